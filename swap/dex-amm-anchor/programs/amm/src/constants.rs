@@ -1 +1,42 @@
-pub const PROTOCOL_FEE: u16 = 5; // 0.05%
\ No newline at end of file
+pub const PROTOCOL_FEE: u16 = 5; // 0.05%
+pub const FLASH_LOAN_FEE: u16 = 9; // 0.09%, left in the vault for LPs
+
+// Ceiling for any LP fee, fixed or dynamic
+pub const MAX_SWAP_FEE: u16 = 1_000; // 10%
+pub const MAX_PROTOCOL_FEE: u16 = 100; // 1%
+
+// Dynamic fees: recorded price movement (in basis points) fades out linearly over
+// VOLATILITY_DECAY_PERIOD and adds one basis point of fee per VOLATILITY_PER_FEE_BPS
+pub const VOLATILITY_DECAY_PERIOD: i64 = 600; // 10 minutes
+pub const VOLATILITY_PER_FEE_BPS: u64 = 10;
+
+// LP minted on the first deposit to an account nobody can sign for, so the
+// pool's supply can never be drained back to a handful of shares
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+// Pause flags stored in PoolConfig::pause_flags
+pub const PAUSE_DEPOSIT: u8 = 1 << 0;
+pub const PAUSE_WITHDRAW: u8 = 1 << 1;
+pub const PAUSE_SWAP: u8 = 1 << 2;
+// Halts deposits and swaps but always lets LPs withdraw pro-rata
+pub const EMERGENCY_MODE: u8 = 1 << 3;
+pub const PAUSE_FLAGS_MASK: u8 = PAUSE_DEPOSIT | PAUSE_WITHDRAW | PAUSE_SWAP | EMERGENCY_MODE;
+
+// StableSwap amplification bounds and ramp limits
+pub const MIN_AMP: u64 = 1;
+pub const MAX_AMP: u64 = 10_000;
+pub const MIN_RAMP_DURATION: i64 = 86_400; // 1 day
+pub const MAX_AMP_CHANGE: u64 = 10;
+
+// Bisection steps used to split a single-sided deposit, enough for ~1e-7 precision
+pub const ZAP_SEARCH_STEPS: u32 = 24;
+
+// Accounts passed per pool in route_swap's remaining_accounts
+pub const ROUTE_LEG_ACCOUNTS: usize = 8;
+
+// TWAP oracle history kept in PoolConfig::observations
+pub const OBSERVATION_CAPACITY: usize = 16;
+pub const OBSERVATION_PERIOD: i64 = 120; // seconds between stored observations
+
+// Scale of Farm::reward_per_share
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
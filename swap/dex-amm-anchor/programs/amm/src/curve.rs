@@ -0,0 +1,328 @@
+use anchor_lang::prelude::*;
+use constant_product_curve::{ConstantProduct, LiquidityPair};
+
+use crate::{
+    constants::ZAP_SEARCH_STEPS,
+    error::AmmDexError,
+    state::{CurveType, PoolConfig},
+};
+
+pub mod stable_swap;
+
+// Fees are expressed in basis points
+pub const FEE_DENOMINATOR: u128 = 10_000;
+
+// Output of trading `amount_in` into the pool after a `fee` basis point LP fee,
+// on whichever curve the pool uses
+pub fn amount_out_for_exact_in(
+    pool_config: &PoolConfig,
+    fee: u16,
+    reserve_in: u64,
+    reserve_out: u64,
+    lp_supply: u64,
+    amount_in: u64,
+) -> Result<u64> {
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(AmmDexError::EmptyReserves.into());
+    }
+    match pool_config.curve_type {
+        CurveType::ConstantProduct => {
+            let mut curve =
+                ConstantProduct::init(reserve_in, reserve_out, lp_supply, fee, None)
+                    .map_err(AmmDexError::from)?;
+            let swap_result = curve
+                .swap(LiquidityPair::X, amount_in, 0)
+                .map_err(AmmDexError::from)?;
+            Ok(swap_result.withdraw)
+        }
+        CurveType::StableSwap => {
+            let net_in =
+                (amount_in as u128 * (FEE_DENOMINATOR - fee as u128) / FEE_DENOMINATOR) as u64;
+            let amp = pool_config.current_amp(Clock::get()?.unix_timestamp);
+            stable_swap::amount_out(reserve_in, reserve_out, net_in, amp)
+        }
+    }
+}
+
+// Input needed to take exactly `amount_out` from the pool after a `fee` basis point cut.
+// Every division rounds up so the invariant never shrinks.
+pub fn amount_in_for_exact_out(
+    pool_config: &PoolConfig,
+    fee: u16,
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_out: u64,
+) -> Result<u64> {
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(AmmDexError::EmptyReserves.into());
+    }
+    if amount_out >= reserve_out {
+        return Err(AmmDexError::InsufficientBalance.into());
+    }
+    let net_in = match pool_config.curve_type {
+        CurveType::ConstantProduct => ceil_div(
+            (reserve_in as u128)
+                .checked_mul(amount_out as u128)
+                .ok_or(AmmDexError::Overflow)?,
+            (reserve_out - amount_out) as u128,
+        )?,
+        CurveType::StableSwap => {
+            let amp = pool_config.current_amp(Clock::get()?.unix_timestamp);
+            stable_swap::amount_in(reserve_in, reserve_out, amount_out, amp)?
+        }
+    };
+    gross_up(net_in, fee)
+}
+
+// Token amounts owed for minting `lp_amount` against existing reserves.
+// Both curves take liquidity pro-rata, rounded up so a depositor never pays less than
+// their share; they only differ in how they price trades.
+pub fn deposit_amounts(
+    reserve_x: u64,
+    reserve_y: u64,
+    lp_supply: u64,
+    lp_amount: u64,
+) -> Result<(u64, u64)> {
+    if lp_supply == 0 {
+        return Err(AmmDexError::EmptyReserves.into());
+    }
+    let share = |reserve: u64| -> Result<u64> {
+        let amount = ceil_div(
+            (reserve as u128)
+                .checked_mul(lp_amount as u128)
+                .ok_or(AmmDexError::Overflow)?,
+            lp_supply as u128,
+        )?;
+        u64::try_from(amount).map_err(|_| AmmDexError::Overflow.into())
+    };
+    Ok((share(reserve_x)?, share(reserve_y)?))
+}
+
+// Part of a single-sided deposit of `amount` to trade across first, so that what is
+// left and what the trade returns match the post-trade reserve ratio. Bisection keeps
+// this curve-agnostic and fee-exact; any rounding dust stays on the input side.
+pub fn zap_swap_amount(
+    pool_config: &PoolConfig,
+    fee: u16,
+    reserve_in: u64,
+    reserve_out: u64,
+    lp_supply: u64,
+    amount: u64,
+) -> Result<u64> {
+    let (mut low, mut high) = (0u64, amount);
+    for _ in 0..ZAP_SEARCH_STEPS {
+        if high - low <= 1 {
+            break;
+        }
+        let mid = low + (high - low) / 2;
+        let net_in = mid - pool_config.protocol_fee_amount(mid);
+        let out = if net_in == 0 {
+            0
+        } else {
+            amount_out_for_exact_in(pool_config, fee, reserve_in, reserve_out, lp_supply, net_in)?
+        };
+        // Still holding too much of the input side relative to the new reserves?
+        let kept = (amount - mid) as u128 * (reserve_out - out) as u128;
+        let received = (out as u128).saturating_mul(reserve_in as u128 + net_in as u128);
+        if kept >= received {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
+}
+
+// Marginal price of the base side in units of the quote side as Q64.64, before fees.
+// An empty side prices at zero so an unfunded pool leaves the oracle flat.
+pub fn spot_price(
+    pool_config: &PoolConfig,
+    reserve_base: u64,
+    reserve_quote: u64,
+    now: i64,
+) -> Result<u128> {
+    if reserve_base == 0 || reserve_quote == 0 {
+        return Ok(0);
+    }
+    match pool_config.curve_type {
+        CurveType::ConstantProduct => Ok(((reserve_quote as u128) << 64) / reserve_base as u128),
+        CurveType::StableSwap => stable_swap::spot_price(
+            reserve_base,
+            reserve_quote,
+            pool_config.current_amp(now),
+        ),
+    }
+}
+
+// Smallest amount that still leaves `net` once a `fee` basis point cut is taken.
+pub fn gross_up(net: u128, fee: u16) -> Result<u64> {
+    if fee as u128 >= FEE_DENOMINATOR {
+        return Err(AmmDexError::InvalidFee.into());
+    }
+    let gross = ceil_div(
+        net.checked_mul(FEE_DENOMINATOR).ok_or(AmmDexError::Overflow)?,
+        FEE_DENOMINATOR - fee as u128,
+    )?;
+    u64::try_from(gross).map_err(|_| AmmDexError::Overflow.into())
+}
+
+// Floor of the square root, by Newton's method
+pub fn integer_sqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = value;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+fn ceil_div(numerator: u128, denominator: u128) -> Result<u128> {
+    if denominator == 0 {
+        return Err(AmmDexError::EmptyReserves.into());
+    }
+    Ok(numerator.div_ceil(denominator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{constants::OBSERVATION_CAPACITY, state::Observation};
+
+    fn constant_product_pool(protocol_fee: u16) -> PoolConfig {
+        PoolConfig {
+            seeds: 0,
+            lp_fee: 30,
+            fee_tier: 30,
+            protocol_fee,
+            lp_bump: 0,
+            pool_config_bump: 0,
+            lp_token_mint: Pubkey::default(),
+            token_x_mint: Pubkey::default(),
+            token_y_mint: Pubkey::default(),
+            owner: None,
+            pending_owner: None,
+            pauser: None,
+            fee_manager: None,
+            emergency_admin: None,
+            fee_recipient: None,
+            reserve_x: 0,
+            reserve_y: 0,
+            protocol_fees_x: 0,
+            protocol_fees_y: 0,
+            pause_flags: 0,
+            curve_type: CurveType::ConstantProduct,
+            amp_initial: 0,
+            amp_target: 0,
+            amp_ramp_start: 0,
+            amp_ramp_end: 0,
+            price_x_cumulative: 0,
+            price_y_cumulative: 0,
+            last_price_x: 0,
+            last_price_y: 0,
+            oracle_updated_at: 0,
+            observation_index: 0,
+            observations: [Observation::default(); OBSERVATION_CAPACITY],
+            active_loan: None,
+            dynamic_fee: None,
+            volatility: 0,
+            volatility_updated_at: 0,
+        }
+    }
+
+    // Whether trading `swap` of a single-sided `amount` still leaves too much of the
+    // input side, the condition the bisection in zap_swap_amount brackets
+    fn keeps_too_much(
+        pool: &PoolConfig,
+        reserve_in: u64,
+        reserve_out: u64,
+        amount: u64,
+        swap: u64,
+    ) -> bool {
+        let net_in = swap - pool.protocol_fee_amount(swap);
+        let out = amount_out_for_exact_in(pool, 30, reserve_in, reserve_out, 1_000_000, net_in)
+            .unwrap();
+        (amount - swap) as u128 * (reserve_out - out) as u128
+            >= out as u128 * (reserve_in as u128 + net_in as u128)
+    }
+
+    #[test]
+    fn zap_swap_amount_is_zero_for_dust() {
+        let pool = constant_product_pool(5);
+        for amount in [0, 1] {
+            let swap =
+                zap_swap_amount(&pool, 30, 1_000_000_000, 1_000_000_000, 1_000_000, amount)
+                    .unwrap();
+            assert_eq!(swap, 0);
+        }
+    }
+
+    #[test]
+    fn zap_swap_amount_brackets_the_balanced_split() {
+        let pool = constant_product_pool(5);
+        let (reserve_in, reserve_out) = (1_000_000_000_000, 1_000_000_000_000);
+        // Below 2^ZAP_SEARCH_STEPS the search narrows down to a single unit
+        let amount = 1_000_000;
+        let swap =
+            zap_swap_amount(&pool, 30, reserve_in, reserve_out, 1_000_000, amount).unwrap();
+        assert!(keeps_too_much(&pool, reserve_in, reserve_out, amount, swap));
+        assert!(!keeps_too_much(&pool, reserve_in, reserve_out, amount, swap + 1));
+        // A trade this small against deep reserves splits close to half
+        assert!((amount * 49 / 100..=amount * 51 / 100).contains(&swap));
+    }
+
+    #[test]
+    fn zap_swap_amount_never_trades_the_whole_deposit() {
+        let pool = constant_product_pool(5);
+        for amount in [2, 3, 1_000, 500_000] {
+            let swap =
+                zap_swap_amount(&pool, 30, 1_000_000, 1_000_000, 1_000_000, amount).unwrap();
+            assert!(swap < amount);
+        }
+    }
+
+    #[test]
+    fn deposit_amounts_cover_the_pro_rata_share() {
+        let cases = [
+            (1_000_000, 1_000_000, 1_000_000, 1),
+            (3_333_333, 7_777_777, 1_000_001, 3),
+            (u64::MAX / 3, u64::MAX / 5, 1_000_000_007, 1),
+            (123_456_789_012_345, 987_654_321_098, 999_999_999_989, 12_345),
+        ];
+        for (reserve_x, reserve_y, lp_supply, lp_amount) in cases {
+            let (x, y) = deposit_amounts(reserve_x, reserve_y, lp_supply, lp_amount).unwrap();
+            for (paid, reserve) in [(x, reserve_x), (y, reserve_y)] {
+                let owed = reserve as u128 * lp_amount as u128;
+                assert!(paid as u128 * lp_supply as u128 >= owed);
+                assert!((paid as u128 - 1) * (lp_supply as u128) < owed);
+            }
+        }
+        assert!(deposit_amounts(1, 1, 0, 1).is_err());
+    }
+
+    #[test]
+    fn integer_sqrt_floors() {
+        for (value, root) in [(0, 0), (1, 1), (2, 1), (3, 1), (4, 2), (15, 3), (16, 4), (17, 4)] {
+            assert_eq!(integer_sqrt(value), root);
+        }
+        assert_eq!(integer_sqrt(u128::MAX), u64::MAX as u128);
+        for value in [999_999_999_999u128, 1 << 100, (1 << 64) + 12_345] {
+            let root = integer_sqrt(value);
+            assert!(root * root <= value);
+            assert!((root + 1).checked_mul(root + 1).is_none_or(|next| next > value));
+        }
+    }
+
+    #[test]
+    fn gross_up_leaves_the_net_after_fees() {
+        for (net, fee) in [(1u128, 30u16), (10_000, 30), (999_999, 5), (123_456_789, 999)] {
+            let gross = gross_up(net, fee).unwrap() as u128;
+            assert!(gross * (FEE_DENOMINATOR - fee as u128) / FEE_DENOMINATOR >= net);
+            assert!((gross - 1) * (FEE_DENOMINATOR - fee as u128) / FEE_DENOMINATOR < net);
+        }
+        assert!(gross_up(1, 10_000).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use anchor_lang::prelude::*;
+
+use crate::error::AmmDexError;
+
+// Two-coin StableSwap invariant: A·n^n·(x + y) + D = A·D·n^n + D^(n+1) / (n^n·x·y).
+// Intermediates are u128, which keeps D^2·A in range for reserves up to about 2^55
+// per side at MAX_AMP (more at lower amplification); larger pools surface
+// AmmDexError::Overflow instead of wrapping.
+const N_COINS: u128 = 2;
+const MAX_ITERATIONS: usize = 64;
+
+fn mul_div(a: u128, b: u128, c: u128) -> Result<u128> {
+    if c == 0 {
+        return Err(AmmDexError::EmptyReserves.into());
+    }
+    Ok(a.checked_mul(b).ok_or(AmmDexError::Overflow)? / c)
+}
+
+// Invariant D for reserves `x` and `y`, solved with Newton's method
+pub fn compute_d(x: u64, y: u64, amp: u64) -> Result<u128> {
+    let (x, y) = (x as u128, y as u128);
+    let sum = x + y;
+    if sum == 0 {
+        return Ok(0);
+    }
+    if x == 0 || y == 0 {
+        return Err(AmmDexError::EmptyReserves.into());
+    }
+    let ann = amp as u128 * N_COINS * N_COINS;
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        let mut d_p = d;
+        d_p = mul_div(d_p, d, x * N_COINS)?;
+        d_p = mul_div(d_p, d, y * N_COINS)?;
+        let d_prev = d;
+        let numerator = ann
+            .checked_mul(sum)
+            .and_then(|v| v.checked_add(d_p * N_COINS))
+            .ok_or(AmmDexError::Overflow)?;
+        let denominator = (ann - 1)
+            .checked_mul(d)
+            .and_then(|v| v.checked_add((N_COINS + 1) * d_p))
+            .ok_or(AmmDexError::Overflow)?;
+        d = mul_div(numerator, d, denominator)?;
+        if d.abs_diff(d_prev) <= 1 {
+            return Ok(d);
+        }
+    }
+    Err(AmmDexError::Overflow.into())
+}
+
+// Balance of the other side that keeps the invariant at `d` when one side holds `x`
+pub fn compute_y(x: u128, d: u128, amp: u64) -> Result<u128> {
+    if x == 0 {
+        return Err(AmmDexError::EmptyReserves.into());
+    }
+    let ann = amp as u128 * N_COINS * N_COINS;
+    let mut c = mul_div(d, d, x * N_COINS)?;
+    c = mul_div(c, d, ann * N_COINS)?;
+    let b = x + d / ann;
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        let numerator = y
+            .checked_mul(y)
+            .and_then(|v| v.checked_add(c))
+            .ok_or(AmmDexError::Overflow)?;
+        let denominator = (2 * y + b)
+            .checked_sub(d)
+            .filter(|v| *v > 0)
+            .ok_or(AmmDexError::Underflow)?;
+        y = numerator / denominator;
+        if y.abs_diff(y_prev) <= 1 {
+            return Ok(y);
+        }
+    }
+    Err(AmmDexError::Overflow.into())
+}
+
+// Output for `amount_in` that has already had its fees taken; rounds down
+pub fn amount_out(reserve_in: u64, reserve_out: u64, amount_in: u64, amp: u64) -> Result<u64> {
+    let d = compute_d(reserve_in, reserve_out, amp)?;
+    let new_reserve_in = reserve_in as u128 + amount_in as u128;
+    let new_reserve_out = compute_y(new_reserve_in, d, amp)?;
+    // The extra unit covers Newton's rounding so the invariant never shrinks
+    let out = (reserve_out as u128).saturating_sub(new_reserve_out + 1);
+    Ok(out as u64)
+}
+
+// Fee-free input needed to take `amount_out`; rounds up
+pub fn amount_in(reserve_in: u64, reserve_out: u64, amount_out: u64, amp: u64) -> Result<u128> {
+    if amount_out.saturating_add(1) >= reserve_out {
+        return Err(AmmDexError::InsufficientBalance.into());
+    }
+    let d = compute_d(reserve_in, reserve_out, amp)?;
+    // Holds back the same extra unit as amount_out, so the input it asks for always
+    // buys at least `amount_out` there
+    let new_reserve_out = (reserve_out - amount_out - 1) as u128;
+    let new_reserve_in = compute_y(new_reserve_out, d, amp)? + 1;
+    Ok(new_reserve_in.saturating_sub(reserve_in as u128))
+}
+
+// Marginal price of `base` in units of `quote` as Q64.64, fees ignored.
+// Differentiating the invariant gives quote·(Ann·base + K) / (base·(Ann·quote + K))
+// where K = D + Ann·(base + quote - D).
+pub fn spot_price(base: u64, quote: u64, amp: u64) -> Result<u128> {
+    if base == 0 || quote == 0 {
+        return Ok(0);
+    }
+    let d = compute_d(base, quote, amp)?;
+    let ann = amp as u128 * N_COINS * N_COINS;
+    let (x, y) = (base as u128, quote as u128);
+    let k = d + ann * (x + y).saturating_sub(d);
+    let mut num = ann * x + k;
+    let mut den = ann * y + k;
+    // The correction factor stays within Ann of 1, so dropping low bits from both
+    // sides keeps it accurate while bounding the products below
+    while num >> 64 != 0 || den >> 64 != 0 {
+        num >>= 1;
+        den >>= 1;
+    }
+    let ratio = (y << 64) / x;
+    Ok((ratio / den)
+        .saturating_mul(num)
+        .saturating_add(ratio % den * num / den))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMP: u64 = 100;
+
+    #[test]
+    fn compute_d_is_the_sum_when_balanced() {
+        for reserve in [1u64, 1_000, 1_000_000_000_000] {
+            let d = compute_d(reserve, reserve, AMP).unwrap();
+            assert!(d.abs_diff(2 * reserve as u128) <= 1);
+        }
+        assert_eq!(compute_d(0, 0, AMP).unwrap(), 0);
+        assert!(compute_d(0, 1_000, AMP).is_err());
+    }
+
+    #[test]
+    fn compute_d_sits_between_the_product_and_sum_invariants() {
+        let (x, y) = (1_000_000_000u64, 4_000_000_000u64);
+        let d = compute_d(x, y, AMP).unwrap();
+        // 2·sqrt(xy) at A = 0, x + y as A grows without bound
+        assert!(d > 4_000_000_000 && d < 5_000_000_000);
+        assert!(compute_d(x, y, 1).unwrap() < d);
+        assert!(compute_d(x, y, 10_000).unwrap() > d);
+    }
+
+    #[test]
+    fn amount_out_never_shrinks_the_invariant() {
+        let (reserve_in, reserve_out) = (1_000_000_000u64, 1_500_000_000u64);
+        let d = compute_d(reserve_in, reserve_out, AMP).unwrap();
+        for amount_in in [1u64, 1_000, 1_000_000, 500_000_000] {
+            let out = amount_out(reserve_in, reserve_out, amount_in, AMP).unwrap();
+            let d_after = compute_d(reserve_in + amount_in, reserve_out - out, AMP).unwrap();
+            assert!(d_after >= d);
+        }
+    }
+
+    #[test]
+    fn amount_out_is_near_one_to_one_around_the_peg() {
+        let out = amount_out(1_000_000_000_000, 1_000_000_000_000, 1_000_000, AMP).unwrap();
+        assert!(out <= 1_000_000);
+        assert!(out >= 999_990);
+    }
+
+    #[test]
+    fn amount_in_covers_the_requested_output() {
+        let (reserve_in, reserve_out) = (2_000_000_000u64, 1_000_000_000u64);
+        let d = compute_d(reserve_in, reserve_out, AMP).unwrap();
+        for out in [1u64, 1_000, 1_000_000, 900_000_000] {
+            let amount_in = amount_in(reserve_in, reserve_out, out, AMP).unwrap() as u64;
+            assert!(amount_out(reserve_in, reserve_out, amount_in, AMP).unwrap() >= out);
+            // The trade never shrinks the invariant
+            assert!(compute_d(reserve_in + amount_in, reserve_out - out, AMP).unwrap() >= d);
+        }
+        assert!(amount_in(reserve_in, reserve_out, reserve_out, AMP).is_err());
+    }
+
+    #[test]
+    fn spot_price_is_one_when_balanced() {
+        let price = spot_price(1_000_000_000, 1_000_000_000, AMP).unwrap();
+        assert!(price.abs_diff(1u128 << 64) <= 1 << 20);
+        assert_eq!(spot_price(0, 1_000, AMP).unwrap(), 0);
+    }
+
+    #[test]
+    fn spot_price_moves_against_the_heavier_side() {
+        let base_heavy = spot_price(2_000_000_000, 1_000_000_000, AMP).unwrap();
+        let quote_heavy = spot_price(1_000_000_000, 2_000_000_000, AMP).unwrap();
+        assert!(base_heavy < 1u128 << 64);
+        assert!(quote_heavy > 1u128 << 64);
+    }
+}
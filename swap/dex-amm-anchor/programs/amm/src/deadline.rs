@@ -0,0 +1,14 @@
+use anchor_lang::prelude::*;
+
+use crate::error::AmmDexError;
+
+// Rejects a trade that lands after the caller's `deadline`, a unix timestamp, so a
+// transaction held back in the mempool cannot execute at a stale price. None opts out.
+pub fn check_deadline(deadline: Option<i64>) -> Result<()> {
+    if let Some(deadline) = deadline {
+        if Clock::get()?.unix_timestamp > deadline {
+            return Err(AmmDexError::DeadlineExceeded.into());
+        }
+    }
+    Ok(())
+}
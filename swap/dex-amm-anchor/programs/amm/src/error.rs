@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use constant_product_curve::CurveError;
 
 #[error_code]
 pub enum PoolConfigError{
@@ -10,6 +11,28 @@ pub enum PoolConfigError{
     InvalidAmount,
     #[msg("Pool is locked")]
     PoolLocked,
+    #[msg("Token mints must be in canonical order")]
+    InvalidMintOrder,
+    #[msg("Deposits are paused")]
+    DepositsPaused,
+    #[msg("Withdrawals are paused")]
+    WithdrawalsPaused,
+    #[msg("Swaps are paused")]
+    SwapsPaused,
+    #[msg("Invalid pause flags")]
+    InvalidPauseFlags,
+    #[msg("Invalid amplification coefficient")]
+    InvalidAmplification,
+    #[msg("Invalid amplification ramp")]
+    InvalidAmpRamp,
+    #[msg("First deposit must mint more than the minimum liquidity")]
+    InsufficientInitialLiquidity,
+    #[msg("Dynamic fee bounds must satisfy min <= max <= cap")]
+    InvalidFeeBounds,
+    #[msg("Fee exceeds its cap")]
+    FeeTooHigh,
+    #[msg("Pool still holds liquidity or tokens")]
+    PoolNotEmpty,
 }
 
 #[error_code]
@@ -20,5 +43,50 @@ pub enum AmmDexError{
     SlippageToleranceExceeded,
     #[msg("Invalid Authority")]
     InvalidAuthority,
+    #[msg("Pool reserves are empty")]
+    EmptyReserves,
+    #[msg("Arithmetic overflow")]
+    Overflow,
+    #[msg("Arithmetic underflow")]
+    Underflow,
+    #[msg("Swap output is zero")]
+    ZeroOutput,
+    #[msg("Invalid curve precision")]
+    InvalidPrecision,
+    #[msg("Invalid fee amount")]
+    InvalidFee,
+    #[msg("Insufficient pool balance")]
+    InsufficientBalance,
+    #[msg("Invalid swap route")]
+    InvalidRoute,
+    #[msg("Not enough price history for the requested window")]
+    OracleWindowUnavailable,
+    #[msg("A flash loan is open on this pool")]
+    FlashLoanActive,
+    #[msg("No flash loan is open on this pool")]
+    FlashLoanNotActive,
+    #[msg("Flash loan must be followed by flash_repay in the same transaction")]
+    MissingFlashRepay,
+    #[msg("Flash loan was not repaid with its fee")]
+    FlashLoanNotRepaid,
+    #[msg("Transaction landed after its deadline")]
+    DeadlineExceeded,
+    #[msg("Not enough LP staked")]
+    InsufficientStake,
+    #[msg("No rewards to claim")]
+    NothingToClaim,
+}
 
+impl From<CurveError> for AmmDexError {
+    fn from(error: CurveError) -> AmmDexError {
+        match error {
+            CurveError::InvalidPrecision => AmmDexError::InvalidPrecision,
+            CurveError::Overflow => AmmDexError::Overflow,
+            CurveError::Underflow => AmmDexError::Underflow,
+            CurveError::InvalidFeeAmount => AmmDexError::InvalidFee,
+            CurveError::InsufficientBalance => AmmDexError::InsufficientBalance,
+            CurveError::ZeroBalance => AmmDexError::EmptyReserves,
+            CurveError::SlippageLimitExceeded => AmmDexError::SlippageToleranceExceeded,
+        }
+    }
 }
\ No newline at end of file
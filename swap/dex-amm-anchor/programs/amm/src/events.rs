@@ -0,0 +1,183 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{ConfigParam, CurveType};
+
+// Emitted through self-CPI so indexers read them from inner instructions, not logs.
+// Reserves are the pool's tracked reserves after the action.
+
+#[event]
+pub struct PoolInitialized {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub owner: Option<Pubkey>,
+    pub token_x_mint: Pubkey,
+    pub token_y_mint: Pubkey,
+    pub lp_token_mint: Pubkey,
+    pub curve_type: CurveType,
+    pub amp: u64,
+    pub lp_fee: u16,
+    pub protocol_fee: u16,
+}
+
+#[event]
+pub struct LiquidityAdded {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lp_amount: u64,
+    // Fees on the internal trade of a single-sided deposit; zero otherwise
+    pub lp_fee: u16,
+    pub protocol_fee: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+#[event]
+pub struct LiquidityRemoved {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lp_amount: u64,
+    // Fees on the internal trade of a single-sided withdraw; zero otherwise
+    pub lp_fee: u16,
+    pub protocol_fee: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+#[event]
+pub struct Swapped {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub lp_pair_x: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    // LP fee rate charged, in basis points; moves with volatility under dynamic fees
+    pub lp_fee: u16,
+    pub protocol_fee: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+// Emitted by the permissionless reconciliation instructions; `user` is absent from
+// `Synced` because sync takes no signer
+#[event]
+pub struct Synced {
+    pub pool_config: Pubkey,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+#[event]
+pub struct Skimmed {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+#[event]
+pub struct ProtocolFeesClaimed {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+// Reserves are unchanged by the loan itself; the fee joins them on repay
+#[event]
+pub struct FlashLoanTaken {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub lp_pair_x: bool,
+    pub amount: u64,
+    pub fee: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+#[event]
+pub struct FlashLoanRepaid {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub lp_pair_x: bool,
+    pub fee: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+#[event]
+pub struct PoolClosed {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+}
+
+#[event]
+pub struct PoolUpdated {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub param: ConfigParam,
+}
+
+// `pending_owner` is None when the owner withdraws a proposal
+#[event]
+pub struct OwnershipProposed {
+    pub pool_config: Pubkey,
+    pub owner: Pubkey,
+    pub pending_owner: Option<Pubkey>,
+}
+
+#[event]
+pub struct OwnershipTransferred {
+    pub pool_config: Pubkey,
+    pub previous_owner: Option<Pubkey>,
+    pub new_owner: Pubkey,
+}
+
+#[event]
+pub struct FarmCreated {
+    pub farm: Pubkey,
+    pub pool_config: Pubkey,
+    pub reward_mint: Pubkey,
+    pub emission_rate: u64,
+}
+
+// `total_staked` is the farm's total after the change
+#[event]
+pub struct Staked {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub total_staked: u64,
+}
+
+#[event]
+pub struct Unstaked {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub total_staked: u64,
+}
+
+#[event]
+pub struct RewardsClaimed {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+// `amount` is what reached the reward vault, after any transfer fee
+#[event]
+pub struct RewardsFunded {
+    pub pool_config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub emission_rate: u64,
+    pub rewards_remaining: u64,
+}
@@ -0,0 +1,162 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::{AmmDexError, PoolConfigError},
+    events::ProtocolFeesClaimed,
+    state::PoolConfig,
+};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimProtocolFees<'info> {
+    #[account(mut)]
+    pub fee_recipient: Signer<'info>,
+
+    #[account(mint::token_program = token_x_program)]
+    pub token_x_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_y_program)]
+    pub token_y_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = fee_recipient,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = fee_recipient,
+        associated_token::token_program = token_x_program,
+    )]
+    pub fee_recipient_x_token: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = fee_recipient,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = fee_recipient,
+        associated_token::token_program = token_y_program,
+    )]
+    pub fee_recipient_y_token: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_x_program,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_y_program,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"pool-config",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
+        bump = pool_config.pool_config_bump,
+        has_one = token_x_mint,
+        has_one = token_y_mint,
+    )]
+    pub pool_config: Account<'info, PoolConfig>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_x_program: Interface<'info, TokenInterface>,
+    pub token_y_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimProtocolFees<'info> {
+    pub fn handle_claim_protocol_fees(&mut self) -> Result<ProtocolFeesClaimed> {
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        require!(
+            self.pool_config.fee_recipient == Some(self.fee_recipient.key()),
+            AmmDexError::InvalidAuthority
+        );
+        // The loan recorded the vault balances; paying out now would make the borrower
+        // overpay on repay
+        if self.pool_config.active_loan.is_some() {
+            return Err(AmmDexError::FlashLoanActive.into());
+        }
+
+        let claim_x = self.pool_config.protocol_fees_x;
+        let claim_y = self.pool_config.protocol_fees_y;
+        if claim_x == 0 && claim_y == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+
+        // Pay out accrued fees
+        if claim_x > 0 {
+            self.transfer_from_vault(
+                &self.token_x_vault,
+                &self.fee_recipient_x_token,
+                &self.token_x_mint,
+                &self.token_x_program,
+                claim_x,
+            )?;
+        }
+        if claim_y > 0 {
+            self.transfer_from_vault(
+                &self.token_y_vault,
+                &self.fee_recipient_y_token,
+                &self.token_y_mint,
+                &self.token_y_program,
+                claim_y,
+            )?;
+        }
+
+        self.pool_config.protocol_fees_x = 0;
+        self.pool_config.protocol_fees_y = 0;
+
+        Ok(ProtocolFeesClaimed {
+            pool_config: self.pool_config.key(),
+            user: self.fee_recipient.key(),
+            amount_x: claim_x,
+            amount_y: claim_y,
+            reserve_x: self.pool_config.reserve_x,
+            reserve_y: self.pool_config.reserve_y,
+        })
+    }
+
+    fn transfer_from_vault(
+        &self,
+        from: &InterfaceAccount<'info, TokenAccount>,
+        to: &InterfaceAccount<'info, TokenAccount>,
+        mint: &InterfaceAccount<'info, Mint>,
+        token_program: &Interface<'info, TokenInterface>,
+        amount: u64,
+    ) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: from.to_account_info(),
+            mint: mint.to_account_info(),
+            to: to.to_account_info(),
+            authority: self.pool_config.to_account_info(),
+        };
+
+        let pool_seeds = self.pool_config.seeds.to_le_bytes();
+        let seeds = &[
+            b"pool-config".as_ref(),
+            self.pool_config.token_x_mint.as_ref(),
+            self.pool_config.token_y_mint.as_ref(),
+            pool_seeds.as_ref(),
+            &[self.pool_config.pool_config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx =
+            CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer_checked(cpi_ctx, amount, mint.decimals)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{close_account, CloseAccount, Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    error::{AmmDexError, PoolConfigError},
+    events::PoolClosed,
+    state::{PoolConfig, PoolRegistration},
+};
+
+// Reclaims the rent of a pool that was created but never funded. A funded pool can't
+// close, since MINIMUM_LIQUIDITY keeps its LP supply above zero for good. The LP mint
+// and its metadata stay behind (classic SPL mints cannot be closed), so the same
+// pair and seeds can never be initialized again.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(mint::token_program = token_x_program)]
+    pub token_x_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_y_program)]
+    pub token_y_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_x_program,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_y_program,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [
+            b"pool-config",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
+        bump = pool_config.pool_config_bump,
+        has_one = token_x_mint,
+        has_one = token_y_mint,
+    )]
+    pub pool_config: Account<'info, PoolConfig>,
+
+    // Freed so the pair can be launched again at this fee tier
+    #[account(
+        mut,
+        close = owner,
+        seeds = [
+            b"pool-registration",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            pool_config.fee_tier.to_le_bytes().as_ref(),
+        ],
+        bump = pool_registration.bump,
+    )]
+    pub pool_registration: Account<'info, PoolRegistration>,
+
+    #[account(
+        seeds = [b"lp-token", pool_config.key().as_ref()],
+        bump = pool_config.lp_bump,
+    )]
+    pub lp_token: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA that owns the locked LP account; signs only to close it once empty
+    #[account(seeds = [b"locked-liquidity", pool_config.key().as_ref()], bump)]
+    pub locked_liquidity: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = lp_token,
+        associated_token::authority = locked_liquidity,
+        associated_token::token_program = token_program,
+    )]
+    pub locked_lp_token_ac: InterfaceAccount<'info, TokenAccount>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_x_program: Interface<'info, TokenInterface>,
+    pub token_y_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> ClosePool<'info> {
+    // Only a pool nobody has a claim on can go: no LP outstanding and nothing in the
+    // vaults, unclaimed protocol fees and donations included. Once a deposit has
+    // locked MINIMUM_LIQUIDITY the supply never returns to zero, so in practice this
+    // reclaims pools that were created but never funded.
+    pub fn handle_close_pool(&mut self, bump: &ClosePoolBumps) -> Result<PoolClosed> {
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        require!(
+            self.pool_config.owner == Some(self.owner.key()),
+            AmmDexError::InvalidAuthority
+        );
+        let vaults_empty = self.token_x_vault.amount == 0 && self.token_y_vault.amount == 0;
+        if self.lp_token.supply != 0 || !vaults_empty {
+            return Err(PoolConfigError::PoolNotEmpty.into());
+        }
+
+        let pool_seeds = self.pool_config.seeds.to_le_bytes();
+        let seeds = &[
+            b"pool-config".as_ref(),
+            self.pool_config.token_x_mint.as_ref(),
+            self.pool_config.token_y_mint.as_ref(),
+            pool_seeds.as_ref(),
+            &[self.pool_config.pool_config_bump],
+        ];
+        let authority = self.pool_config.to_account_info();
+        self.close_token_account(&self.token_x_vault, &authority, &self.token_x_program, seeds)?;
+        self.close_token_account(&self.token_y_vault, &authority, &self.token_y_program, seeds)?;
+
+        let pool_key = self.pool_config.key();
+        let locked_seeds = &[
+            b"locked-liquidity".as_ref(),
+            pool_key.as_ref(),
+            &[bump.locked_liquidity],
+        ];
+        self.close_token_account(
+            &self.locked_lp_token_ac,
+            &self.locked_liquidity.to_account_info(),
+            &self.token_program,
+            locked_seeds,
+        )?;
+
+        Ok(PoolClosed {
+            pool_config: pool_key,
+            user: self.owner.key(),
+        })
+    }
+
+    fn close_token_account(
+        &self,
+        account: &InterfaceAccount<'info, TokenAccount>,
+        authority: &AccountInfo<'info>,
+        token_program: &Interface<'info, TokenInterface>,
+        seeds: &[&[u8]],
+    ) -> Result<()> {
+        let cpi_accounts = CloseAccount {
+            account: account.to_account_info(),
+            destination: self.owner.to_account_info(),
+            authority: authority.clone(),
+        };
+        let signer_seeds = &[seeds];
+        let cpi_ctx =
+            CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+        close_account(cpi_ctx)?;
+        Ok(())
+    }
+}
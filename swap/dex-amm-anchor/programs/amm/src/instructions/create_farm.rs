@@ -0,0 +1,105 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    error::{AmmDexError, PoolConfigError},
+    events::FarmCreated,
+    state::{Farm, PoolConfig},
+};
+
+// Opens the LP staking farm for a pool; it emits nothing until fund_rewards fills it
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CreateFarm<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [
+            b"pool-config",
+            pool_config.token_x_mint.as_ref(),
+            pool_config.token_y_mint.as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
+        bump = pool_config.pool_config_bump,
+    )]
+    pub pool_config: Account<'info, PoolConfig>,
+
+    #[account(
+        seeds = [b"lp-token", pool_config.key().as_ref()],
+        bump = pool_config.lp_bump,
+    )]
+    pub lp_token: InterfaceAccount<'info, Mint>,
+
+    #[account(mint::token_program = reward_token_program)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Farm::INIT_SPACE,
+        seeds = [b"farm", pool_config.key().as_ref()],
+        bump
+    )]
+    pub farm: Account<'info, Farm>,
+
+    // Holds the staked LP
+    #[account(
+        init,
+        payer = owner,
+        associated_token::mint = lp_token,
+        associated_token::authority = farm,
+        associated_token::token_program = token_program,
+    )]
+    pub farm_lp_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = owner,
+        associated_token::mint = reward_mint,
+        associated_token::authority = farm,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub reward_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> CreateFarm<'info> {
+    pub fn handle_create_farm(
+        &mut self,
+        emission_rate: u64,
+        bump: &CreateFarmBumps,
+    ) -> Result<FarmCreated> {
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        require!(
+            self.pool_config.owner == Some(self.owner.key()),
+            AmmDexError::InvalidAuthority
+        );
+        self.farm.set_inner(Farm {
+            pool_config: self.pool_config.key(),
+            reward_mint: self.reward_mint.key(),
+            emission_rate,
+            reward_per_share: 0,
+            last_update: Clock::get()?.unix_timestamp,
+            total_staked: 0,
+            rewards_remaining: 0,
+            bump: bump.farm,
+        });
+
+        Ok(FarmCreated {
+            farm: self.farm.key(),
+            pool_config: self.pool_config.key(),
+            reward_mint: self.reward_mint.key(),
+            emission_rate,
+        })
+    }
+}
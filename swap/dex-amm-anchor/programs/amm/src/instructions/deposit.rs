@@ -1,37 +1,148 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{transfer, mint_to, MintTo, Mint, Token, TokenAccount, Transfer},
+    token_interface::{
+        mint_to, transfer_checked, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked,
+    },
 };
-use constant_product_curve::ConstantProduct;
 
 use crate::{
+    deadline::check_deadline,
+    constants::MINIMUM_LIQUIDITY,
+    curve::{deposit_amounts, integer_sqrt, zap_swap_amount},
     error::{AmmDexError, PoolConfigError},
+    events::LiquidityAdded,
+    instructions::swap::{quote_exact_in, SwapQuote},
+    oracle,
     state::PoolConfig,
 };
 
+// Tokens a deposit of `amount` LP pulls from the user. The first deposit sets the
+// price, so it takes the maximums as given. Shared with quote_deposit.
+pub fn deposit_token_amounts(
+    pool_config: &PoolConfig,
+    lp_supply: u64,
+    amount: u64,
+    max_token_x: u64,
+    max_token_y: u64,
+) -> Result<(u64, u64)> {
+    if lp_supply == 0 {
+        // first deposit sets the price; LP is minted from what arrives, not `amount`
+        return Ok((max_token_x, max_token_y));
+    }
+    deposit_amounts(pool_config.reserve_x, pool_config.reserve_y, lp_supply, amount)
+}
+
+// LP minted to the user for the share of the pool that was actually paid for
+pub fn deposit_lp_amount(
+    pool_config: &PoolConfig,
+    lp_supply: u64,
+    amount: u64,
+    (deposit_x, deposit_y): (u64, u64),
+    (received_x, received_y): (u64, u64),
+) -> Result<u64> {
+    let lp_amount = if lp_supply == 0 {
+        // The first LP is the geometric mean of what arrived, less MINIMUM_LIQUIDITY
+        // locked away for good. Inflating the share price by donation then costs the
+        // attacker a thousand times what it can take from later depositors.
+        let liquidity = integer_sqrt(received_x as u128 * received_y as u128) as u64;
+        if liquidity <= MINIMUM_LIQUIDITY {
+            return Err(PoolConfigError::InsufficientInitialLiquidity.into());
+        }
+        liquidity - MINIMUM_LIQUIDITY
+    } else if received_x == deposit_x && received_y == deposit_y {
+        amount
+    } else {
+        let lp_x = received_x as u128 * lp_supply as u128 / pool_config.reserve_x as u128;
+        let lp_y = received_y as u128 * lp_supply as u128 / pool_config.reserve_y as u128;
+        lp_x.min(lp_y).min(amount as u128) as u64
+    };
+    if lp_amount == 0 {
+        return Err(PoolConfigError::InvalidAmount.into());
+    }
+    Ok(lp_amount)
+}
+
+// LP minted for `received` of one side alone, and the internal trade that balances it.
+// Shared with quote_deposit_single.
+pub fn deposit_single_amounts(
+    pool_config: &PoolConfig,
+    lp_supply: u64,
+    lp_pair_x: bool,
+    received: u64,
+) -> Result<(u64, SwapQuote)> {
+    let (reserve_in, reserve_out) = if lp_pair_x {
+        (pool_config.reserve_x, pool_config.reserve_y)
+    } else {
+        (pool_config.reserve_y, pool_config.reserve_x)
+    };
+    let lp_fee = pool_config.swap_fee(Clock::get()?.unix_timestamp);
+    let swap_amount =
+        zap_swap_amount(pool_config, lp_fee, reserve_in, reserve_out, lp_supply, received)?;
+    // Too small to trade any of it, so no LP could be minted for the other side
+    if swap_amount == 0 {
+        return Err(PoolConfigError::InvalidAmount.into());
+    }
+    let quote = quote_exact_in(pool_config, lp_supply, lp_pair_x, swap_amount)?;
+    let reserve_in_after = reserve_in as u128 + (swap_amount - quote.protocol_fee) as u128;
+    let reserve_out_after = (reserve_out - quote.amount_out) as u128;
+    let lp_in = (received - swap_amount) as u128 * lp_supply as u128 / reserve_in_after;
+    let lp_out = quote.amount_out as u128 * lp_supply as u128 / reserve_out_after;
+    let lp_amount = u64::try_from(lp_in.min(lp_out)).map_err(|_| AmmDexError::Overflow)?;
+    Ok((lp_amount, quote))
+}
+
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Deposit<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
 
-    pub token_x_mint: Account<'info, Mint>,
-    pub token_y_mint: Account<'info, Mint>,
+    #[account(mint::token_program = token_x_program)]
+    pub token_x_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_y_program)]
+    pub token_y_mint: InterfaceAccount<'info, Mint>,
 
-    #[account(associated_token::mint = token_x_mint, associated_token::authority = user)]
-    pub user_x_token: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_x_program,
+    )]
+    pub user_x_token: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(associated_token::mint = token_y_mint, associated_token::authority = user)]
-    pub user_y_token: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_y_program,
+    )]
+    pub user_y_token: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(associated_token::mint = token_x_mint, associated_token::authority = pool_config)]
-    pub token_x_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_x_program,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(associated_token::mint = token_y_mint, associated_token::authority = pool_config)]
-    pub token_y_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_y_program,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        seeds = [b"pool-config", pool_config.owner.as_ref().unwrap().as_ref()],
+        mut,
+        seeds = [
+            b"pool-config",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
         bump = pool_config.pool_config_bump,
         has_one = token_x_mint,
         has_one = token_y_mint,
@@ -39,18 +150,38 @@ pub struct Deposit<'info> {
     pub pool_config: Account<'info, PoolConfig>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_x_program: Interface<'info, TokenInterface>,
+    pub token_y_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 
-    pub lp_token: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"lp-token", pool_config.key().as_ref()],
+        bump = pool_config.lp_bump,
+    )]
+    pub lp_token: InterfaceAccount<'info, Mint>,
 
     #[account(
         init_if_needed,
         payer = user,
         associated_token::mint = lp_token,
         associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_lp_token_ac: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: PDA that holds the locked minimum liquidity; nothing ever signs for it
+    #[account(seeds = [b"locked-liquidity", pool_config.key().as_ref()], bump)]
+    pub locked_liquidity: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = lp_token,
+        associated_token::authority = locked_liquidity,
+        associated_token::token_program = token_program,
     )]
-    pub user_lp_token_ac: Account<'info, TokenAccount>,
+    pub locked_lp_token_ac: InterfaceAccount<'info, TokenAccount>,
 }
 
 impl<'info> Deposit<'info> {
@@ -59,84 +190,208 @@ impl<'info> Deposit<'info> {
         amount: u64,
         max_token_x: u64,
         max_token_y: u64,
-    ) -> Result<()> {
+        deadline: Option<i64>,
+    ) -> Result<LiquidityAdded> {
         // Amount Checks & other checks
+        check_deadline(deadline)?;
         if self.pool_config.owner.is_none() {
             return Err(PoolConfigError::PoolNotInitialized.into());
         }
-        if self.pool_config.is_locked {
-            return Err(PoolConfigError::PoolLocked.into());
+        if self.pool_config.deposits_paused() {
+            return Err(PoolConfigError::DepositsPaused.into());
+        }
+        if self.pool_config.active_loan.is_some() {
+            return Err(AmmDexError::FlashLoanActive.into());
         }
         if amount == 0 || max_token_x == 0 || max_token_y == 0 {
             return Err(PoolConfigError::InvalidAmount.into());
         }
 
         // Calculate required deposit amounts
-        let (deposit_x, deposit_y) = if self.lp_token.supply == 0
-            && self.token_x_vault.amount == 0
-            && self.token_y_vault.amount == 0
-        {
-            // first deposit
-            (max_token_x, max_token_y)
-        } else {
-            let dep = ConstantProduct::xy_deposit_amounts_from_l(
-                self.token_x_vault.amount,
-                self.token_y_vault.amount,
-                self.lp_token.supply,
-                amount,
-                6,
-            )
-            .map_err(|_| PoolConfigError::InvalidAmount)?;
-            (dep.x, dep.y)
-        };
+        let supply = self.lp_token.supply;
+        let (deposit_x, deposit_y) =
+            deposit_token_amounts(&self.pool_config, supply, amount, max_token_x, max_token_y)?;
 
         if deposit_x > max_token_x || deposit_y > max_token_y {
             return Err(AmmDexError::SlippageToleranceExceeded.into());
         }
 
-        // Transfer tokens to vault
-        self.transfer_tokens(&self.user_x_token, &self.token_x_vault, deposit_x)?;
-        self.transfer_tokens(&self.user_y_token, &self.token_y_vault, deposit_y)?;
+        // Transfer tokens to vault, measuring what arrived in case a mint charges a transfer fee
+        let vault_x_before = self.token_x_vault.amount;
+        let vault_y_before = self.token_y_vault.amount;
+        self.transfer_tokens(
+            &self.user_x_token,
+            &self.token_x_vault,
+            &self.token_x_mint,
+            &self.token_x_program,
+            deposit_x,
+        )?;
+        self.transfer_tokens(
+            &self.user_y_token,
+            &self.token_y_vault,
+            &self.token_y_mint,
+            &self.token_y_program,
+            deposit_y,
+        )?;
+        self.token_x_vault.reload()?;
+        self.token_y_vault.reload()?;
+        let received_x = self.token_x_vault.amount - vault_x_before;
+        let received_y = self.token_y_vault.amount - vault_y_before;
+
+        let lp_amount = deposit_lp_amount(
+            &self.pool_config,
+            supply,
+            amount,
+            (deposit_x, deposit_y),
+            (received_x, received_y),
+        )?;
+        if supply == 0 {
+            self.mint_lp_tokens(&self.locked_lp_token_ac, MINIMUM_LIQUIDITY)?;
+        }
 
         // Mint LP tokens to user
-        self.mint_lp_tokens(amount)?;
+        self.mint_lp_tokens(&self.user_lp_token_ac, lp_amount)?;
 
-        msg!(
-            "Deposit complete: X = {}, Y = {}, LP = {}",
-            deposit_x,
-            deposit_y,
-            amount
-        );
+        self.pool_config.reserve_x = self
+            .pool_config
+            .reserve_x
+            .checked_add(received_x)
+            .ok_or(AmmDexError::Overflow)?;
+        self.pool_config.reserve_y = self
+            .pool_config
+            .reserve_y
+            .checked_add(received_y)
+            .ok_or(AmmDexError::Overflow)?;
+        oracle::update(&mut self.pool_config, Clock::get()?.unix_timestamp)?;
 
-        Ok(())
+        Ok(LiquidityAdded {
+            pool_config: self.pool_config.key(),
+            user: self.user.key(),
+            amount_x: received_x,
+            amount_y: received_y,
+            lp_amount,
+            lp_fee: 0,
+            protocol_fee: 0,
+            reserve_x: self.pool_config.reserve_x,
+            reserve_y: self.pool_config.reserve_y,
+        })
+    }
+
+    // Deposits one side only: trades part of it across the pool, then mints LP for the
+    // balanced remainder. The trade pays the usual LP and protocol fees.
+    pub fn handle_deposit_single(
+        &mut self,
+        lp_pair_x: bool,
+        amount: u64,
+        min_lp_out: u64,
+        deadline: Option<i64>,
+    ) -> Result<LiquidityAdded> {
+        // Amount Checks & other checks
+        check_deadline(deadline)?;
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        if self.pool_config.deposits_paused() {
+            return Err(PoolConfigError::DepositsPaused.into());
+        }
+        self.pool_config.check_swap_allowed()?;
+        if amount == 0 || min_lp_out == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+        let supply = self.lp_token.supply;
+        let vault_x = self.token_x_vault.amount;
+        let vault_y = self.token_y_vault.amount;
+        let (reserve_x, reserve_y) = (self.pool_config.reserve_x, self.pool_config.reserve_y);
+        // There is no ratio to zap into before the first two-sided deposit
+        if supply == 0 || reserve_x == 0 || reserve_y == 0 {
+            return Err(AmmDexError::EmptyReserves.into());
+        }
+
+        // Transfer the input to its vault, measuring what arrived
+        let received = if lp_pair_x {
+            self.transfer_tokens(
+                &self.user_x_token,
+                &self.token_x_vault,
+                &self.token_x_mint,
+                &self.token_x_program,
+                amount,
+            )?;
+            self.token_x_vault.reload()?;
+            self.token_x_vault.amount - vault_x
+        } else {
+            self.transfer_tokens(
+                &self.user_y_token,
+                &self.token_y_vault,
+                &self.token_y_mint,
+                &self.token_y_program,
+                amount,
+            )?;
+            self.token_y_vault.reload()?;
+            self.token_y_vault.amount - vault_y
+        };
+
+        // The traded half never leaves the pool: the output stays in its vault
+        // and is credited to the depositor along with the untraded remainder
+        let (lp_amount, quote) =
+            deposit_single_amounts(&self.pool_config, supply, lp_pair_x, received)?;
+        if lp_amount < min_lp_out {
+            return Err(AmmDexError::SlippageToleranceExceeded.into());
+        }
+
+        self.mint_lp_tokens(&self.user_lp_token_ac, lp_amount)?;
+
+        // Trade and deposit net out to the whole input joining the reserves less the
+        // protocol fee, while the output side is left where it was
+        self.pool_config
+            .apply_swap(lp_pair_x, received, 0, quote.protocol_fee)?;
+        oracle::update(&mut self.pool_config, Clock::get()?.unix_timestamp)?;
+
+        let (amount_x, amount_y) = if lp_pair_x { (received, 0) } else { (0, received) };
+        Ok(LiquidityAdded {
+            pool_config: self.pool_config.key(),
+            user: self.user.key(),
+            amount_x,
+            amount_y,
+            lp_amount,
+            lp_fee: quote.lp_fee,
+            protocol_fee: quote.protocol_fee,
+            reserve_x: self.pool_config.reserve_x,
+            reserve_y: self.pool_config.reserve_y,
+        })
     }
 
     fn transfer_tokens(
         &self,
-        from: &Account<'info, TokenAccount>,
-        to: &Account<'info, TokenAccount>,
+        from: &InterfaceAccount<'info, TokenAccount>,
+        to: &InterfaceAccount<'info, TokenAccount>,
+        mint: &InterfaceAccount<'info, Mint>,
+        token_program: &Interface<'info, TokenInterface>,
         amount: u64,
     ) -> Result<()> {
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from: from.to_account_info(),
+            mint: mint.to_account_info(),
             to: to.to_account_info(),
             authority: self.user.to_account_info(),
         };
-        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
-        transfer(cpi_ctx, amount)?;
+        let cpi_ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+        transfer_checked(cpi_ctx, amount, mint.decimals)?;
         Ok(())
     }
 
-    fn mint_lp_tokens(&self, amount: u64) -> Result<()> {
+    fn mint_lp_tokens(&self, to: &InterfaceAccount<'info, TokenAccount>, amount: u64) -> Result<()> {
         let cpi_accounts = MintTo {
             mint: self.lp_token.to_account_info(),
-            to: self.user_lp_token_ac.to_account_info(),
+            to: to.to_account_info(),
             authority: self.pool_config.to_account_info(),
         };
 
+        let pool_seeds = self.pool_config.seeds.to_le_bytes();
         let seeds = &[
-            b"pool-config",
-            self.pool_config.owner.as_ref().unwrap().as_ref(),
+            b"pool-config".as_ref(),
+            self.pool_config.token_x_mint.as_ref(),
+            self.pool_config.token_y_mint.as_ref(),
+            pool_seeds.as_ref(),
             &[self.pool_config.pool_config_bump],
         ];
         let signer_seeds = &[&seeds[..]];
@@ -0,0 +1,246 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::sysvar::instructions::{
+        self as instructions_sysvar, load_current_index_checked, load_instruction_at_checked,
+    },
+    Discriminator,
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    constants::FLASH_LOAN_FEE,
+    error::{AmmDexError, PoolConfigError},
+    events::{FlashLoanRepaid, FlashLoanTaken},
+    oracle,
+    state::{ActiveLoan, PoolConfig},
+    transfer_fee::amount_before_transfer_fee,
+};
+
+// Shared by flash_loan and flash_repay, which must name the same pool
+#[event_cpi]
+#[derive(Accounts)]
+pub struct FlashLoan<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mint::token_program = token_x_program)]
+    pub token_x_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_y_program)]
+    pub token_y_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_x_program,
+    )]
+    pub user_x_token: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_y_program,
+    )]
+    pub user_y_token: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_x_program,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_y_program,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"pool-config",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
+        bump = pool_config.pool_config_bump,
+        has_one = token_x_mint,
+        has_one = token_y_mint,
+    )]
+    pub pool_config: Account<'info, PoolConfig>,
+
+    /// CHECK: address is checked; read to find the flash_repay that closes the loan
+    #[account(address = instructions_sysvar::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_x_program: Interface<'info, TokenInterface>,
+    pub token_y_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> FlashLoan<'info> {
+    pub fn handle_flash_loan(&mut self, lp_pair_x: bool, amount: u64) -> Result<FlashLoanTaken> {
+        // Amount Checks & other checks
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        if self.pool_config.swaps_paused() {
+            return Err(PoolConfigError::SwapsPaused.into());
+        }
+        if self.pool_config.active_loan.is_some() {
+            return Err(AmmDexError::FlashLoanActive.into());
+        }
+        if amount == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+        // Only the LPs' reserves are lent, never accrued protocol fees or donations
+        let (reserve, vault_balance) = if lp_pair_x {
+            (self.pool_config.reserve_x, self.token_x_vault.amount)
+        } else {
+            (self.pool_config.reserve_y, self.token_y_vault.amount)
+        };
+        if amount > reserve {
+            return Err(AmmDexError::InsufficientBalance.into());
+        }
+        self.check_repay_follows()?;
+
+        let fee = (amount as u128 * FLASH_LOAN_FEE as u128).div_ceil(10_000) as u64;
+        self.pool_config.active_loan = Some(ActiveLoan {
+            lp_pair_x,
+            vault_balance,
+            fee,
+        });
+        self.transfer_from_vault(lp_pair_x, amount)?;
+
+        Ok(FlashLoanTaken {
+            pool_config: self.pool_config.key(),
+            user: self.user.key(),
+            lp_pair_x,
+            amount,
+            fee,
+            reserve_x: self.pool_config.reserve_x,
+            reserve_y: self.pool_config.reserve_y,
+        })
+    }
+
+    pub fn handle_flash_repay(&mut self) -> Result<FlashLoanRepaid> {
+        let loan = self
+            .pool_config
+            .active_loan
+            .ok_or(AmmDexError::FlashLoanNotActive)?;
+        let (vault, mint) = if loan.lp_pair_x {
+            (&self.token_x_vault, &self.token_x_mint)
+        } else {
+            (&self.token_y_vault, &self.token_y_mint)
+        };
+        // Whatever the borrower already sent back counts towards the debt
+        let owed_balance = loan
+            .vault_balance
+            .checked_add(loan.fee)
+            .ok_or(AmmDexError::Overflow)?;
+        let owed = owed_balance.saturating_sub(vault.amount);
+        if owed > 0 {
+            let amount = amount_before_transfer_fee(mint, owed)?;
+            self.transfer_to_vault(loan.lp_pair_x, amount)?;
+        }
+
+        let vault = if loan.lp_pair_x {
+            &mut self.token_x_vault
+        } else {
+            &mut self.token_y_vault
+        };
+        vault.reload()?;
+        if vault.amount < owed_balance {
+            return Err(AmmDexError::FlashLoanNotRepaid.into());
+        }
+        self.pool_config.active_loan = None;
+
+        // The fee joins the reserves, so it accrues to LPs; any overpayment is left to skim
+        let reserve = if loan.lp_pair_x {
+            &mut self.pool_config.reserve_x
+        } else {
+            &mut self.pool_config.reserve_y
+        };
+        *reserve = reserve.checked_add(loan.fee).ok_or(AmmDexError::Overflow)?;
+        oracle::update(&mut self.pool_config, Clock::get()?.unix_timestamp)?;
+
+        Ok(FlashLoanRepaid {
+            pool_config: self.pool_config.key(),
+            user: self.user.key(),
+            lp_pair_x: loan.lp_pair_x,
+            fee: loan.fee,
+            reserve_x: self.pool_config.reserve_x,
+            reserve_y: self.pool_config.reserve_y,
+        })
+    }
+
+    // A later top-level instruction in this transaction must be flash_repay on this pool;
+    // if it fails or never runs the whole transaction, loan included, is rolled back
+    fn check_repay_follows(&self) -> Result<()> {
+        let instructions = self.instructions.to_account_info();
+        let current = load_current_index_checked(&instructions)? as usize;
+        let mut index = current + 1;
+        while let Ok(ix) = load_instruction_at_checked(index, &instructions) {
+            if ix.program_id == crate::ID
+                && ix.data.starts_with(crate::instruction::FlashRepay::DISCRIMINATOR)
+                && ix.accounts.iter().any(|meta| meta.pubkey == self.pool_config.key())
+            {
+                return Ok(());
+            }
+            index += 1;
+        }
+        Err(AmmDexError::MissingFlashRepay.into())
+    }
+
+    fn transfer_from_vault(&self, lp_pair_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, token_program) = if lp_pair_x {
+            (&self.token_x_vault, &self.user_x_token, &self.token_x_mint, &self.token_x_program)
+        } else {
+            (&self.token_y_vault, &self.user_y_token, &self.token_y_mint, &self.token_y_program)
+        };
+        let pool_seeds = self.pool_config.seeds.to_le_bytes();
+        let seeds = &[
+            b"pool-config".as_ref(),
+            self.pool_config.token_x_mint.as_ref(),
+            self.pool_config.token_y_mint.as_ref(),
+            pool_seeds.as_ref(),
+            &[self.pool_config.pool_config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = TransferChecked {
+            from: from.to_account_info(),
+            mint: mint.to_account_info(),
+            to: to.to_account_info(),
+            authority: self.pool_config.to_account_info(),
+        };
+        let cpi_ctx =
+            CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer_checked(cpi_ctx, amount, mint.decimals)?;
+        Ok(())
+    }
+
+    fn transfer_to_vault(&self, lp_pair_x: bool, amount: u64) -> Result<()> {
+        let (from, to, mint, token_program) = if lp_pair_x {
+            (&self.user_x_token, &self.token_x_vault, &self.token_x_mint, &self.token_x_program)
+        } else {
+            (&self.user_y_token, &self.token_y_vault, &self.token_y_mint, &self.token_y_program)
+        };
+        let cpi_accounts = TransferChecked {
+            from: from.to_account_info(),
+            mint: mint.to_account_info(),
+            to: to.to_account_info(),
+            authority: self.user.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+        transfer_checked(cpi_ctx, amount, mint.decimals)?;
+        Ok(())
+    }
+}
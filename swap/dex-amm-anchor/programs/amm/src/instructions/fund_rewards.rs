@@ -0,0 +1,111 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::{AmmDexError, PoolConfigError},
+    events::RewardsFunded,
+    state::{Farm, PoolConfig},
+};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct FundRewards<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [
+            b"pool-config",
+            pool_config.token_x_mint.as_ref(),
+            pool_config.token_y_mint.as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
+        bump = pool_config.pool_config_bump,
+    )]
+    pub pool_config: Account<'info, PoolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"farm", pool_config.key().as_ref()],
+        bump = farm.bump,
+        has_one = reward_mint,
+    )]
+    pub farm: Account<'info, Farm>,
+
+    #[account(mint::token_program = reward_token_program)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = owner,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub owner_reward_token: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = farm,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub reward_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> FundRewards<'info> {
+    // Tops up the rewards and, when given, sets a new emission rate. Emissions up to
+    // now are booked at the old rate first.
+    pub fn handle_fund_rewards(
+        &mut self,
+        amount: u64,
+        emission_rate: Option<u64>,
+    ) -> Result<RewardsFunded> {
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        require!(
+            self.pool_config.owner == Some(self.owner.key()),
+            AmmDexError::InvalidAuthority
+        );
+        self.farm.accrue(Clock::get()?.unix_timestamp)?;
+
+        let mut received = 0;
+        if amount > 0 {
+            // Only what lands counts, in case the reward mint charges a transfer fee
+            let before = self.reward_vault.amount;
+            let cpi_accounts = TransferChecked {
+                from: self.owner_reward_token.to_account_info(),
+                mint: self.reward_mint.to_account_info(),
+                to: self.reward_vault.to_account_info(),
+                authority: self.owner.to_account_info(),
+            };
+            let cpi_ctx =
+                CpiContext::new(self.reward_token_program.to_account_info(), cpi_accounts);
+            transfer_checked(cpi_ctx, amount, self.reward_mint.decimals)?;
+            self.reward_vault.reload()?;
+            received = self.reward_vault.amount - before;
+            self.farm.rewards_remaining = self
+                .farm
+                .rewards_remaining
+                .checked_add(received)
+                .ok_or(AmmDexError::Overflow)?;
+        }
+        if let Some(rate) = emission_rate {
+            self.farm.emission_rate = rate;
+        }
+
+        Ok(RewardsFunded {
+            pool_config: self.pool_config.key(),
+            user: self.owner.key(),
+            amount: received,
+            emission_rate: self.farm.emission_rate,
+            rewards_remaining: self.farm.rewards_remaining,
+        })
+    }
+}
@@ -1,52 +1,153 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    metadata::{
+        create_metadata_accounts_v3,
+        mpl_token_metadata::{types::DataV2, MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH},
+        CreateMetadataAccountsV3, Metadata, MetadataAccount,
+    },
+    token_2022::spl_token_2022::{
+        extension::{BaseStateWithExtensions, StateWithExtensions},
+        state::Mint as MintState,
+    },
+    token_2022_extensions::spl_token_metadata_interface::state::TokenMetadata,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
 
-use crate::state::PoolConfig;
-use crate::constants::PROTOCOL_FEE;
+use crate::error::{AmmDexError, PoolConfigError};
+use crate::events::PoolInitialized;
+use crate::state::{CurveType, Factory, Observation, PoolConfig, PoolRegistration};
+use crate::constants::{MAX_AMP, MAX_SWAP_FEE, MIN_AMP, OBSERVATION_CAPACITY, PROTOCOL_FEE};
 
+#[event_cpi]
 #[derive(Accounts)]
+#[instruction(seeds: u64, fee: u16)]
 pub struct Init<'info> {
     #[account(mut)]
     owner: Signer<'info>,
+    // Pairs are stored in canonical order so each (x, y, seeds) maps to one pool
+    #[account(
+        mint::token_program = token_x_program,
+        constraint = token_x_mint.key() < token_y_mint.key() @ PoolConfigError::InvalidMintOrder,
+    )]
+    token_x_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_y_program)]
+    token_y_mint: InterfaceAccount<'info, Mint>,
+
+    // Metaplex metadata of each side, when it has one; the LP token is named after them
+    #[account(
+        seeds = [
+            b"metadata",
+            token_metadata_program.key().as_ref(),
+            token_x_mint.key().as_ref(),
+        ],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    token_x_metadata: Option<Box<Account<'info, MetadataAccount>>>,
+    #[account(
+        seeds = [
+            b"metadata",
+            token_metadata_program.key().as_ref(),
+            token_y_mint.key().as_ref(),
+        ],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    token_y_metadata: Option<Box<Account<'info, MetadataAccount>>>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + PoolConfig::INIT_SPACE,
+        seeds = [
+            b"pool-config",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            seeds.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pool_config: Account<'info, PoolConfig>,
+
+    #[account(mut, seeds = [b"factory"], bump = factory.bump)]
+    factory: Account<'info, Factory>,
+
+    // One pool per pair and fee tier: a second `init` here fails as the account exists
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + PoolRegistration::INIT_SPACE,
+        seeds = [
+            b"pool-registration",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            fee.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pool_registration: Account<'info, PoolRegistration>,
+
     #[account(
         init,
         payer = owner,
-        seeds = [b"lp-token", owner.key().as_ref()],
+        seeds = [b"lp-token", pool_config.key().as_ref()],
         bump,
         mint::decimals = 6,
         mint::authority = pool_config,
+        mint::token_program = token_program,
     )]
-    lp_token: Account<'info, Mint>,
-    token_x_mint: Account<'info, Mint>,
-    token_y_mint: Account<'info, Mint>,
-    
+    lp_token: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Metaplex metadata PDA for the LP mint, created by the metadata program
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), lp_token.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    lp_token_metadata: UncheckedAccount<'info>,
+
     #[account(
         init,
         payer = owner,
-        space = 8 + PoolConfig::INIT_SPACE,
-        seeds = [b"pool-config", owner.key().as_ref()],
-        bump
+        associated_token::mint = token_x_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_x_program,
     )]
-    pool_config: Account<'info, PoolConfig>,
+    token_x_vault: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         init,
-        payer = pool_config,
-        token::mint = token_x_mint,
-        token::authority = pool_config,
+        payer = owner,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_y_program,
     )]
-    token_x_vault: Account<'info, TokenAccount>,
+    token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: PDA that holds the locked minimum liquidity; it only signs in close_pool,
+    /// to close its LP account once empty
+    #[account(seeds = [b"locked-liquidity", pool_config.key().as_ref()], bump)]
+    locked_liquidity: UncheckedAccount<'info>,
 
     #[account(
         init,
-        payer = pool_config,
-        token::mint = token_y_mint,
-        token::authority = pool_config,
+        payer = owner,
+        associated_token::mint = lp_token,
+        associated_token::authority = locked_liquidity,
+        associated_token::token_program = token_program,
     )]
-    token_y_vault: Account<'info, TokenAccount>,
+    locked_lp_token_ac: InterfaceAccount<'info, TokenAccount>,
 
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    // Each side of the pair may live on Token or Token-2022; the LP mint uses token_program
+    token_x_program: Interface<'info, TokenInterface>,
+    token_y_program: Interface<'info, TokenInterface>,
+    token_program: Interface<'info, TokenInterface>,
+    associated_token_program: Program<'info, AssociatedToken>,
+    token_metadata_program: Program<'info, Metadata>,
+    rent: Sysvar<'info, Rent>,
 }
 
 impl<'info> Init<'info> {
@@ -56,22 +157,170 @@ impl<'info> Init<'info> {
         bump: &InitBumps,
         fee: u16,
         owner: Option<Pubkey>,
-    ) -> Result<()> {
-        let pool_config = &mut self.pool_config.set_inner(
+        curve_type: CurveType,
+        amp: u64,
+    ) -> Result<PoolInitialized> {
+        if fee > MAX_SWAP_FEE {
+            return Err(PoolConfigError::FeeTooHigh.into());
+        }
+        // An ownerless pool could never be used or closed, so it would squat the pair's
+        // registration for this fee tier forever
+        if owner.is_none() {
+            return Err(PoolConfigError::InvalidOwner.into());
+        }
+        // Amplification only means something on a StableSwap curve
+        let amp = match curve_type {
+            CurveType::ConstantProduct => 0,
+            CurveType::StableSwap => {
+                if !(MIN_AMP..=MAX_AMP).contains(&amp) {
+                    return Err(PoolConfigError::InvalidAmplification.into());
+                }
+                amp
+            }
+        };
+        let now = Clock::get()?.unix_timestamp;
+        // The oracle starts flat from the first observation so early windows average in zeroes
+        let mut observations = [Observation::default(); OBSERVATION_CAPACITY];
+        observations[0].timestamp = now;
+        self.pool_config.set_inner(
             PoolConfig {
                 seeds,
                 lp_fee: fee,
+                fee_tier: fee,
                 protocol_fee: PROTOCOL_FEE,
                 lp_bump: bump.lp_token,
                 pool_config_bump: bump.pool_config,
                 lp_token_mint: self.lp_token.key(),
                 token_x_mint: self.token_x_mint.key(),
                 token_y_mint: self.token_y_mint.key(),
-                is_locked: false,
                 owner,
+                pending_owner: None,
+                pauser: None,
+                fee_manager: None,
+                emergency_admin: None,
+                fee_recipient: owner,
+                reserve_x: 0,
+                reserve_y: 0,
+                protocol_fees_x: 0,
+                protocol_fees_y: 0,
+                pause_flags: 0,
+                curve_type,
+                amp_initial: amp,
+                amp_target: amp,
+                amp_ramp_start: now,
+                amp_ramp_end: now,
+                price_x_cumulative: 0,
+                price_y_cumulative: 0,
+                last_price_x: 0,
+                last_price_y: 0,
+                oracle_updated_at: now,
+                observation_index: 0,
+                observations,
+                active_loan: None,
+                dynamic_fee: None,
+                volatility: 0,
+                volatility_updated_at: now,
             }
         );
-        msg!("Pool Config initialized: {:?}", pool_config);
-        Ok(())
+        self.pool_registration.set_inner(PoolRegistration {
+            pool_config: self.pool_config.key(),
+            token_x_mint: self.token_x_mint.key(),
+            token_y_mint: self.token_y_mint.key(),
+            fee_tier: fee,
+            index: self.factory.pool_count,
+            bump: bump.pool_registration,
+        });
+        self.factory.pool_count = self
+            .factory
+            .pool_count
+            .checked_add(1)
+            .ok_or(AmmDexError::Overflow)?;
+        self.create_lp_token_metadata(seeds)?;
+
+        Ok(PoolInitialized {
+            pool_config: self.pool_config.key(),
+            user: self.owner.key(),
+            owner,
+            token_x_mint: self.token_x_mint.key(),
+            token_y_mint: self.token_y_mint.key(),
+            lp_token_mint: self.lp_token.key(),
+            curve_type,
+            amp,
+            lp_fee: fee,
+            protocol_fee: PROTOCOL_FEE,
+        })
     }
-}
\ No newline at end of file
+
+    // Names the LP mint after its pair, e.g. "SOL-USDC LP" / "SOLUSDC-LP", so wallets
+    // don't show an anonymous token. The pool PDA is both mint and update authority.
+    fn create_lp_token_metadata(&self, seeds: u64) -> Result<()> {
+        let x = mint_symbol(&self.token_x_mint, self.token_x_metadata.as_deref())?;
+        let y = mint_symbol(&self.token_y_mint, self.token_y_metadata.as_deref())?;
+        let pair = format!("{}{}", x, y);
+        let data = DataV2 {
+            name: truncate(&format!("{}-{} LP", x, y), MAX_NAME_LENGTH).to_string(),
+            symbol: format!("{}-LP", truncate(&pair, MAX_SYMBOL_LENGTH - 3)),
+            uri: String::new(),
+            seller_fee_basis_points: 0,
+            creators: None,
+            collection: None,
+            uses: None,
+        };
+
+        let pool_seeds = seeds.to_le_bytes();
+        let seeds = &[
+            b"pool-config".as_ref(),
+            self.pool_config.token_x_mint.as_ref(),
+            self.pool_config.token_y_mint.as_ref(),
+            pool_seeds.as_ref(),
+            &[self.pool_config.pool_config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = CreateMetadataAccountsV3 {
+            metadata: self.lp_token_metadata.to_account_info(),
+            mint: self.lp_token.to_account_info(),
+            mint_authority: self.pool_config.to_account_info(),
+            payer: self.owner.to_account_info(),
+            update_authority: self.pool_config.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            rent: self.rent.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_metadata_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        create_metadata_accounts_v3(cpi_ctx, data, true, true, None)
+    }
+}
+
+// Symbol of a pair token: its Token-2022 metadata extension, then its Metaplex metadata,
+// then the first characters of its address when it has neither
+fn mint_symbol(
+    mint: &InterfaceAccount<'_, Mint>,
+    metaplex: Option<&Account<'_, MetadataAccount>>,
+) -> Result<String> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+    let symbol = match mint_state.get_variable_len_extension::<TokenMetadata>() {
+        Ok(metadata) => metadata.symbol,
+        Err(_) => metaplex.map(|metadata| metadata.symbol.clone()).unwrap_or_default(),
+    };
+    // Metaplex pads its strings with zero bytes
+    let symbol = symbol.trim_matches(char::from(0)).trim();
+    if symbol.is_empty() {
+        return Ok(mint.key().to_string()[..4].to_string());
+    }
+    Ok(symbol.to_string())
+}
+
+// Longest prefix of `value` within `max` bytes that doesn't split a character
+fn truncate(value: &str, max: usize) -> &str {
+    let mut end = value.len().min(max);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
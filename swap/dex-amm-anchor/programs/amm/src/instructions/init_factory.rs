@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Factory;
+
+// Creates the program-wide registry every pool is recorded in; runs once per deployment
+#[derive(Accounts)]
+pub struct InitFactory<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Factory::INIT_SPACE,
+        seeds = [b"factory"],
+        bump
+    )]
+    pub factory: Account<'info, Factory>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitFactory<'info> {
+    pub fn handle_initialize_factory(&mut self, bump: &InitFactoryBumps) -> Result<()> {
+        self.factory.set_inner(Factory {
+            pool_count: 0,
+            bump: bump.factory,
+        });
+        Ok(())
+    }
+}
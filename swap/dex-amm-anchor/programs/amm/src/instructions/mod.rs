@@ -1,11 +1,35 @@
 pub mod init;
+pub mod init_factory;
 pub mod deposit;
 pub mod withdraw;
 pub mod swap;
 pub mod update;
+pub mod claim_protocol_fees;
+pub mod route_swap;
+pub mod observe;
+pub mod quote;
+pub mod flash_loan;
+pub mod sync;
+pub mod skim;
+pub mod close_pool;
+pub mod create_farm;
+pub mod fund_rewards;
+pub mod stake;
 
 pub use init::*;
+pub use init_factory::*;
 pub use deposit::*;
 pub use withdraw::*;
 pub use swap::*;
-pub use update::*;
\ No newline at end of file
+pub use update::*;
+pub use claim_protocol_fees::*;
+pub use route_swap::*;
+pub use observe::*;
+pub use quote::*;
+pub use flash_loan::*;
+pub use sync::*;
+pub use skim::*;
+pub use close_pool::*;
+pub use create_farm::*;
+pub use fund_rewards::*;
+pub use stake::*;
\ No newline at end of file
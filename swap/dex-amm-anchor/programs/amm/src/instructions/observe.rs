@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+use crate::{oracle, state::{PoolConfig, Twap}};
+
+#[derive(Accounts)]
+pub struct Observe<'info> {
+    #[account(
+        seeds = [
+            b"pool-config",
+            pool_config.token_x_mint.as_ref(),
+            pool_config.token_y_mint.as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
+        bump = pool_config.pool_config_bump,
+    )]
+    pub pool_config: Account<'info, PoolConfig>,
+}
+
+impl<'info> Observe<'info> {
+    // Read-only; Anchor hands the result back to the caller as return data
+    pub fn handle_observe(&self, window: u32) -> Result<Twap> {
+        let now = Clock::get()?.unix_timestamp;
+        oracle::observe(&self.pool_config, window, now)
+    }
+}
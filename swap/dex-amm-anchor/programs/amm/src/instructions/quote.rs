@@ -0,0 +1,309 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{
+    curve::spot_price,
+    error::{AmmDexError, PoolConfigError},
+    instructions::{
+        deposit::{deposit_lp_amount, deposit_single_amounts, deposit_token_amounts},
+        swap::{quote_exact_in, quote_exact_out, SwapQuote},
+        withdraw::{withdraw_single_amount, withdraw_token_amounts},
+    },
+    state::PoolConfig,
+    transfer_fee::{amount_after_transfer_fee, amount_before_transfer_fee},
+};
+
+// What `swap` would do for the same input right now
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SwapEstimate {
+    // Input that reaches the pool after any Token-2022 transfer fee
+    pub amount_in: u64,
+    // Compare against `min_swap_amount`; the output mint may still take its transfer fee
+    pub amount_out: u64,
+    pub lp_fee: u16,
+    pub protocol_fee: u64,
+    // How far the trade moves the spot price of the input token, in basis points
+    pub price_impact_bps: u64,
+}
+
+// What `swap_exact_out` would charge for the same output right now
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SwapExactOutEstimate {
+    // Compare against `max_amount_in`; includes any Token-2022 transfer fee on the input
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub lp_fee: u16,
+    pub protocol_fee: u64,
+    pub price_impact_bps: u64,
+}
+
+// What `deposit` or `deposit_single` would pull and mint for the same arguments right now
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct DepositEstimate {
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub lp_amount: u64,
+    // Fees and price impact of the internal trade of a single-sided deposit; zero otherwise
+    pub lp_fee: u16,
+    pub protocol_fee: u64,
+    pub price_impact_bps: u64,
+}
+
+// What `withdraw` or `withdraw_single` would pay out for the same LP amount right now
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct WithdrawEstimate {
+    pub amount_x: u64,
+    pub amount_y: u64,
+    // Fees and price impact of the internal trade of a single-sided withdraw; zero otherwise
+    pub lp_fee: u16,
+    pub protocol_fee: u64,
+    pub price_impact_bps: u64,
+}
+
+// Read-only quotes for frontends: meant for simulateTransaction, with the result
+// handed back as return data. Each one runs the same pricing code as the instruction
+// it mirrors, so a quote and the trade that follows it cannot disagree.
+#[derive(Accounts)]
+pub struct Quote<'info> {
+    pub token_x_mint: InterfaceAccount<'info, Mint>,
+    pub token_y_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [
+            b"pool-config",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
+        bump = pool_config.pool_config_bump,
+        has_one = token_x_mint,
+        has_one = token_y_mint,
+    )]
+    pub pool_config: Account<'info, PoolConfig>,
+
+    #[account(
+        seeds = [b"lp-token", pool_config.key().as_ref()],
+        bump = pool_config.lp_bump,
+    )]
+    pub lp_token: InterfaceAccount<'info, Mint>,
+}
+
+impl<'info> Quote<'info> {
+    pub fn handle_quote_swap(&self, lp_pair_x: bool, amount: u64) -> Result<SwapEstimate> {
+        // Amount Checks & other checks
+        self.pool_config.check_swap_allowed()?;
+        if amount == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+        let mint_in = if lp_pair_x { &self.token_x_mint } else { &self.token_y_mint };
+        let received = amount_after_transfer_fee(mint_in, amount)?;
+        let quote = quote_exact_in(&self.pool_config, self.lp_token.supply, lp_pair_x, received)?;
+
+        Ok(SwapEstimate {
+            amount_in: received,
+            amount_out: quote.amount_out,
+            lp_fee: quote.lp_fee,
+            protocol_fee: quote.protocol_fee,
+            price_impact_bps: price_impact_bps(&self.pool_config, lp_pair_x, &quote)?,
+        })
+    }
+
+    pub fn handle_quote_swap_exact_out(
+        &self,
+        lp_pair_x: bool,
+        amount_out: u64,
+    ) -> Result<SwapExactOutEstimate> {
+        // Amount Checks & other checks
+        self.pool_config.check_swap_allowed()?;
+        if amount_out == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+        let mint_in = if lp_pair_x { &self.token_x_mint } else { &self.token_y_mint };
+        let quote = quote_exact_out(&self.pool_config, lp_pair_x, amount_out)?;
+
+        Ok(SwapExactOutEstimate {
+            amount_in: amount_before_transfer_fee(mint_in, quote.amount_in)?,
+            amount_out,
+            lp_fee: quote.lp_fee,
+            protocol_fee: quote.protocol_fee,
+            price_impact_bps: price_impact_bps(&self.pool_config, lp_pair_x, &quote)?,
+        })
+    }
+
+    pub fn handle_quote_deposit(
+        &self,
+        amount: u64,
+        max_token_x: u64,
+        max_token_y: u64,
+    ) -> Result<DepositEstimate> {
+        // Amount Checks & other checks
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        if self.pool_config.deposits_paused() {
+            return Err(PoolConfigError::DepositsPaused.into());
+        }
+        if self.pool_config.active_loan.is_some() {
+            return Err(AmmDexError::FlashLoanActive.into());
+        }
+        if amount == 0 || max_token_x == 0 || max_token_y == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+        let supply = self.lp_token.supply;
+        let (deposit_x, deposit_y) =
+            deposit_token_amounts(&self.pool_config, supply, amount, max_token_x, max_token_y)?;
+        let received = (
+            amount_after_transfer_fee(&self.token_x_mint, deposit_x)?,
+            amount_after_transfer_fee(&self.token_y_mint, deposit_y)?,
+        );
+        let lp_amount =
+            deposit_lp_amount(&self.pool_config, supply, amount, (deposit_x, deposit_y), received)?;
+
+        Ok(DepositEstimate {
+            amount_x: deposit_x,
+            amount_y: deposit_y,
+            lp_amount,
+            lp_fee: 0,
+            protocol_fee: 0,
+            price_impact_bps: 0,
+        })
+    }
+
+    pub fn handle_quote_withdraw(&self, amount: u64) -> Result<WithdrawEstimate> {
+        // Amount Checks & other checks
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        if self.pool_config.withdrawals_paused() {
+            return Err(PoolConfigError::WithdrawalsPaused.into());
+        }
+        if self.pool_config.active_loan.is_some() {
+            return Err(AmmDexError::FlashLoanActive.into());
+        }
+        if amount == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+        let (amount_x, amount_y) =
+            withdraw_token_amounts(&self.pool_config, self.lp_token.supply, amount)?;
+
+        Ok(WithdrawEstimate {
+            amount_x,
+            amount_y,
+            lp_fee: 0,
+            protocol_fee: 0,
+            price_impact_bps: 0,
+        })
+    }
+
+    pub fn handle_quote_deposit_single(
+        &self,
+        lp_pair_x: bool,
+        amount: u64,
+    ) -> Result<DepositEstimate> {
+        // Amount Checks & other checks
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        if self.pool_config.deposits_paused() {
+            return Err(PoolConfigError::DepositsPaused.into());
+        }
+        self.pool_config.check_swap_allowed()?;
+        if amount == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+        let supply = self.lp_token.supply;
+        if supply == 0 || self.pool_config.reserve_x == 0 || self.pool_config.reserve_y == 0 {
+            return Err(AmmDexError::EmptyReserves.into());
+        }
+        let mint_in = if lp_pair_x { &self.token_x_mint } else { &self.token_y_mint };
+        let received = amount_after_transfer_fee(mint_in, amount)?;
+        let (lp_amount, quote) =
+            deposit_single_amounts(&self.pool_config, supply, lp_pair_x, received)?;
+
+        let (amount_x, amount_y) = if lp_pair_x { (amount, 0) } else { (0, amount) };
+        Ok(DepositEstimate {
+            amount_x,
+            amount_y,
+            lp_amount,
+            lp_fee: quote.lp_fee,
+            protocol_fee: quote.protocol_fee,
+            price_impact_bps: price_impact_bps(&self.pool_config, lp_pair_x, &quote)?,
+        })
+    }
+
+    pub fn handle_quote_withdraw_single(
+        &self,
+        lp_pair_x: bool,
+        amount: u64,
+    ) -> Result<WithdrawEstimate> {
+        // Amount Checks & other checks
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        if self.pool_config.withdrawals_paused() {
+            return Err(PoolConfigError::WithdrawalsPaused.into());
+        }
+        self.pool_config.check_swap_allowed()?;
+        if amount == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+        let supply = self.lp_token.supply;
+        let (withdraw_x, withdraw_y) = withdraw_token_amounts(&self.pool_config, supply, amount)?;
+        // Price the internal sale against the reserves the withdraw leaves behind
+        let mut pool_config = Box::new((*self.pool_config).clone());
+        pool_config.reserve_x = pool_config
+            .reserve_x
+            .checked_sub(withdraw_x)
+            .ok_or(AmmDexError::Underflow)?;
+        pool_config.reserve_y = pool_config
+            .reserve_y
+            .checked_sub(withdraw_y)
+            .ok_or(AmmDexError::Underflow)?;
+        let (amount_out, quote) = withdraw_single_amount(
+            &pool_config,
+            supply - amount,
+            lp_pair_x,
+            (withdraw_x, withdraw_y),
+        )?;
+
+        let (amount_x, amount_y) = if lp_pair_x { (amount_out, 0) } else { (0, amount_out) };
+        // The unwanted side is sold, so the internal trade runs the other way
+        let (lp_fee, protocol_fee, price_impact_bps) = match quote {
+            Some(quote) => (
+                quote.lp_fee,
+                quote.protocol_fee,
+                price_impact_bps(&pool_config, !lp_pair_x, &quote)?,
+            ),
+            None => (0, 0, 0),
+        };
+        Ok(WithdrawEstimate {
+            amount_x,
+            amount_y,
+            lp_fee,
+            protocol_fee,
+            price_impact_bps,
+        })
+    }
+}
+
+// How far a trade moves the spot price of its input token, in basis points, with
+// the reserves as `apply_swap` would leave them
+fn price_impact_bps(pool_config: &PoolConfig, lp_pair_x: bool, quote: &SwapQuote) -> Result<u64> {
+    let (reserve_in, reserve_out) = if lp_pair_x {
+        (pool_config.reserve_x, pool_config.reserve_y)
+    } else {
+        (pool_config.reserve_y, pool_config.reserve_x)
+    };
+    let now = Clock::get()?.unix_timestamp;
+    let price_before = spot_price(pool_config, reserve_in, reserve_out, now)?;
+    let reserve_in_after = reserve_in
+        .checked_add(quote.amount_in - quote.protocol_fee)
+        .ok_or(AmmDexError::Overflow)?;
+    let price_after = spot_price(pool_config, reserve_in_after, reserve_out - quote.amount_out, now)?;
+    let price_impact_bps = price_before
+        .abs_diff(price_after)
+        .saturating_mul(10_000)
+        .checked_div(price_before)
+        .unwrap_or(0);
+    Ok(u64::try_from(price_impact_bps).unwrap_or(u64::MAX))
+}
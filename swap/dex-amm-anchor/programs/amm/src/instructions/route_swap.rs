@@ -0,0 +1,207 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id,
+    token::Token,
+    token_2022::Token2022,
+    token_interface::{Mint, TokenAccount},
+};
+
+use crate::{
+    deadline::check_deadline,
+    constants::ROUTE_LEG_ACCOUNTS,
+    error::{AmmDexError, PoolConfigError},
+    events::Swapped,
+    instructions::swap::{execute_swap, SwapAccounts},
+    state::PoolConfig,
+};
+
+// Each leg of the route is passed through remaining_accounts as
+// [pool_config, token_x_mint, token_y_mint, token_x_vault, token_y_vault,
+//  lp_token, user_x_token, user_y_token]
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RouteSwap<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    // Each mint is moved with whichever of the two programs owns it
+    pub token_program: Program<'info, Token>,
+    pub token_2022_program: Program<'info, Token2022>,
+}
+
+struct RouteLeg<'info> {
+    pool_config: Account<'info, PoolConfig>,
+    token_x_mint: InterfaceAccount<'info, Mint>,
+    token_y_mint: InterfaceAccount<'info, Mint>,
+    token_x_vault: InterfaceAccount<'info, TokenAccount>,
+    token_y_vault: InterfaceAccount<'info, TokenAccount>,
+    lp_token: InterfaceAccount<'info, Mint>,
+    user_x_token: InterfaceAccount<'info, TokenAccount>,
+    user_y_token: InterfaceAccount<'info, TokenAccount>,
+}
+
+impl<'info> RouteLeg<'info> {
+    // Remaining accounts are unchecked, so apply the same constraints Swap does
+    fn load(accounts: &'info [AccountInfo<'info>], user: &Pubkey) -> Result<Self> {
+        let pool_config = Account::<PoolConfig>::try_from(&accounts[0])?;
+        let expected_pool = Pubkey::create_program_address(
+            &[
+                b"pool-config",
+                pool_config.token_x_mint.as_ref(),
+                pool_config.token_y_mint.as_ref(),
+                pool_config.seeds.to_le_bytes().as_ref(),
+                &[pool_config.pool_config_bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| AmmDexError::InvalidRoute)?;
+        require_keys_eq!(pool_config.key(), expected_pool, AmmDexError::InvalidRoute);
+        require_keys_eq!(accounts[1].key(), pool_config.token_x_mint, AmmDexError::InvalidRoute);
+        require_keys_eq!(accounts[2].key(), pool_config.token_y_mint, AmmDexError::InvalidRoute);
+        require_keys_eq!(
+            accounts[3].key(),
+            get_associated_token_address_with_program_id(
+                &pool_config.key(),
+                &pool_config.token_x_mint,
+                accounts[1].owner,
+            ),
+            AmmDexError::InvalidRoute
+        );
+        require_keys_eq!(
+            accounts[4].key(),
+            get_associated_token_address_with_program_id(
+                &pool_config.key(),
+                &pool_config.token_y_mint,
+                accounts[2].owner,
+            ),
+            AmmDexError::InvalidRoute
+        );
+        require_keys_eq!(accounts[5].key(), pool_config.lp_token_mint, AmmDexError::InvalidRoute);
+
+        let user_x_token = InterfaceAccount::<TokenAccount>::try_from(&accounts[6])?;
+        let user_y_token = InterfaceAccount::<TokenAccount>::try_from(&accounts[7])?;
+        require_keys_eq!(user_x_token.owner, *user, AmmDexError::InvalidRoute);
+        require_keys_eq!(user_x_token.mint, pool_config.token_x_mint, AmmDexError::InvalidRoute);
+        require_keys_eq!(user_y_token.owner, *user, AmmDexError::InvalidRoute);
+        require_keys_eq!(user_y_token.mint, pool_config.token_y_mint, AmmDexError::InvalidRoute);
+
+        Ok(Self {
+            pool_config,
+            token_x_mint: InterfaceAccount::<Mint>::try_from(&accounts[1])?,
+            token_y_mint: InterfaceAccount::<Mint>::try_from(&accounts[2])?,
+            token_x_vault: InterfaceAccount::<TokenAccount>::try_from(&accounts[3])?,
+            token_y_vault: InterfaceAccount::<TokenAccount>::try_from(&accounts[4])?,
+            lp_token: InterfaceAccount::<Mint>::try_from(&accounts[5])?,
+            user_x_token,
+            user_y_token,
+        })
+    }
+}
+
+impl<'info> RouteSwap<'info> {
+    pub fn handle_route_swap(
+        &self,
+        legs: &'info [AccountInfo<'info>],
+        lp_pair_x: Vec<bool>,
+        amount: u64,
+        min_swap_amount: u64,
+        deadline: Option<i64>,
+    ) -> Result<Vec<Swapped>> {
+        // Amount Checks & other checks
+        check_deadline(deadline)?;
+        if lp_pair_x.is_empty() || legs.len() != lp_pair_x.len() * ROUTE_LEG_ACCOUNTS {
+            return Err(AmmDexError::InvalidRoute.into());
+        }
+        if amount == 0 || min_swap_amount == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+
+        // Each leg spends what the previous one delivered to the user
+        let mut amount_out = amount;
+        let mut next_mint: Option<Pubkey> = None;
+        let mut swaps = Vec::with_capacity(lp_pair_x.len());
+        for (accounts, &pair_x) in legs.chunks(ROUTE_LEG_ACCOUNTS).zip(lp_pair_x.iter()) {
+            let mut leg = RouteLeg::load(accounts, &self.user.key())?;
+            let (mint_in, mint_out) = if pair_x {
+                (leg.pool_config.token_x_mint, leg.pool_config.token_y_mint)
+            } else {
+                (leg.pool_config.token_y_mint, leg.pool_config.token_x_mint)
+            };
+            if let Some(expected) = next_mint {
+                require_keys_eq!(mint_in, expected, AmmDexError::InvalidRoute);
+            }
+            let swapped;
+            (amount_out, swapped) = self.swap_leg(&mut leg, pair_x, amount_out)?;
+            swaps.push(swapped);
+            next_mint = Some(mint_out);
+        }
+
+        // One slippage check for the whole route; failing it reverts every leg
+        if amount_out < min_swap_amount {
+            return Err(AmmDexError::SlippageToleranceExceeded.into());
+        }
+
+        Ok(swaps)
+    }
+
+    // Runs the leg through the same path as swap and returns what reached the user
+    // alongside the leg's event
+    fn swap_leg(
+        &self,
+        leg: &mut RouteLeg<'info>,
+        lp_pair_x: bool,
+        amount: u64,
+    ) -> Result<(u64, Swapped)> {
+        let user_out_before = if lp_pair_x {
+            leg.user_y_token.amount
+        } else {
+            leg.user_x_token.amount
+        };
+        let accounts = if lp_pair_x {
+            SwapAccounts {
+                user: &self.user,
+                pool_config: &mut leg.pool_config,
+                lp_supply: leg.lp_token.supply,
+                mint_in: &leg.token_x_mint,
+                user_in: &leg.user_x_token,
+                vault_in: &mut leg.token_x_vault,
+                token_program_in: self.token_program_for(&leg.token_x_mint),
+                mint_out: &leg.token_y_mint,
+                vault_out: &leg.token_y_vault,
+                user_out: &leg.user_y_token,
+                token_program_out: self.token_program_for(&leg.token_y_mint),
+            }
+        } else {
+            SwapAccounts {
+                user: &self.user,
+                pool_config: &mut leg.pool_config,
+                lp_supply: leg.lp_token.supply,
+                mint_in: &leg.token_y_mint,
+                user_in: &leg.user_y_token,
+                vault_in: &mut leg.token_y_vault,
+                token_program_in: self.token_program_for(&leg.token_y_mint),
+                mint_out: &leg.token_x_mint,
+                vault_out: &leg.token_x_vault,
+                user_out: &leg.user_x_token,
+                token_program_out: self.token_program_for(&leg.token_x_mint),
+            }
+        };
+        let swapped = execute_swap(accounts, lp_pair_x, amount)?;
+        // Persist the booked trade before the next leg reloads the pool
+        leg.pool_config.exit(&crate::ID)?;
+
+        let user_out = if lp_pair_x { &mut leg.user_y_token } else { &mut leg.user_x_token };
+        user_out.reload()?;
+        let delivered = user_out.amount - user_out_before;
+
+        Ok((delivered, swapped))
+    }
+
+    fn token_program_for(&self, mint: &InterfaceAccount<'info, Mint>) -> AccountInfo<'info> {
+        if *mint.to_account_info().owner == Token2022::id() {
+            self.token_2022_program.to_account_info()
+        } else {
+            self.token_program.to_account_info()
+        }
+    }
+}
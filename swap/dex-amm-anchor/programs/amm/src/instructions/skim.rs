@@ -0,0 +1,151 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::{AmmDexError, PoolConfigError},
+    events::Skimmed,
+    state::PoolConfig,
+};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Skim<'info> {
+    pub user: Signer<'info>,
+
+    #[account(mint::token_program = token_x_program)]
+    pub token_x_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_y_program)]
+    pub token_y_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = token_x_mint,
+        token::token_program = token_x_program,
+    )]
+    pub recipient_x_token: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = token_y_mint,
+        token::token_program = token_y_program,
+    )]
+    pub recipient_y_token: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_x_program,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_y_program,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [
+            b"pool-config",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
+        bump = pool_config.pool_config_bump,
+        has_one = token_x_mint,
+        has_one = token_y_mint,
+    )]
+    pub pool_config: Account<'info, PoolConfig>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_x_program: Interface<'info, TokenInterface>,
+    pub token_y_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> Skim<'info> {
+    // Sends whatever the vaults hold beyond the reserves and the unclaimed protocol
+    // fees to the recipient accounts, leaving pricing untouched. Anyone may call it.
+    pub fn handle_skim(&mut self) -> Result<Skimmed> {
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        if self.pool_config.active_loan.is_some() {
+            return Err(AmmDexError::FlashLoanActive.into());
+        }
+        let excess_x = self
+            .token_x_vault
+            .amount
+            .saturating_sub(self.pool_config.reserve_x)
+            .saturating_sub(self.pool_config.protocol_fees_x);
+        let excess_y = self
+            .token_y_vault
+            .amount
+            .saturating_sub(self.pool_config.reserve_y)
+            .saturating_sub(self.pool_config.protocol_fees_y);
+
+        if excess_x > 0 {
+            self.transfer_from_vault(
+                &self.token_x_vault,
+                &self.recipient_x_token,
+                &self.token_x_mint,
+                &self.token_x_program,
+                excess_x,
+            )?;
+        }
+        if excess_y > 0 {
+            self.transfer_from_vault(
+                &self.token_y_vault,
+                &self.recipient_y_token,
+                &self.token_y_mint,
+                &self.token_y_program,
+                excess_y,
+            )?;
+        }
+
+        Ok(Skimmed {
+            pool_config: self.pool_config.key(),
+            user: self.user.key(),
+            amount_x: excess_x,
+            amount_y: excess_y,
+            reserve_x: self.pool_config.reserve_x,
+            reserve_y: self.pool_config.reserve_y,
+        })
+    }
+
+    fn transfer_from_vault(
+        &self,
+        from: &InterfaceAccount<'info, TokenAccount>,
+        to: &InterfaceAccount<'info, TokenAccount>,
+        mint: &InterfaceAccount<'info, Mint>,
+        token_program: &Interface<'info, TokenInterface>,
+        amount: u64,
+    ) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: from.to_account_info(),
+            mint: mint.to_account_info(),
+            to: to.to_account_info(),
+            authority: self.pool_config.to_account_info(),
+        };
+
+        let pool_seeds = self.pool_config.seeds.to_le_bytes();
+        let seeds = &[
+            b"pool-config".as_ref(),
+            self.pool_config.token_x_mint.as_ref(),
+            self.pool_config.token_y_mint.as_ref(),
+            pool_seeds.as_ref(),
+            &[self.pool_config.pool_config_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx =
+            CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer_checked(cpi_ctx, amount, mint.decimals)?;
+        Ok(())
+    }
+}
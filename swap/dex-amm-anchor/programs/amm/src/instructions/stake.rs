@@ -0,0 +1,234 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::{AmmDexError, PoolConfigError},
+    events::{RewardsClaimed, Staked, Unstaked},
+    state::{Farm, PoolConfig, StakePosition},
+};
+
+// Shared by stake, unstake and claim_rewards
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Stake<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [
+            b"pool-config",
+            pool_config.token_x_mint.as_ref(),
+            pool_config.token_y_mint.as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
+        bump = pool_config.pool_config_bump,
+    )]
+    pub pool_config: Account<'info, PoolConfig>,
+
+    #[account(
+        seeds = [b"lp-token", pool_config.key().as_ref()],
+        bump = pool_config.lp_bump,
+    )]
+    pub lp_token: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"farm", pool_config.key().as_ref()],
+        bump = farm.bump,
+        has_one = reward_mint,
+    )]
+    pub farm: Account<'info, Farm>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + StakePosition::INIT_SPACE,
+        seeds = [b"stake-position", farm.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+
+    #[account(
+        mut,
+        associated_token::mint = lp_token,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_lp_token_ac: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = lp_token,
+        associated_token::authority = farm,
+        associated_token::token_program = token_program,
+    )]
+    pub farm_lp_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mint::token_program = reward_token_program)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint,
+        associated_token::authority = user,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub user_reward_token: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = farm,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub reward_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> Stake<'info> {
+    pub fn handle_stake(&mut self, amount: u64, bump: &StakeBumps) -> Result<Staked> {
+        if amount == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+        self.open_position(bump);
+        self.checkpoint()?;
+
+        let cpi_accounts = TransferChecked {
+            from: self.user_lp_token_ac.to_account_info(),
+            mint: self.lp_token.to_account_info(),
+            to: self.farm_lp_vault.to_account_info(),
+            authority: self.user.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        transfer_checked(cpi_ctx, amount, self.lp_token.decimals)?;
+
+        self.stake_position.amount = self
+            .stake_position
+            .amount
+            .checked_add(amount)
+            .ok_or(AmmDexError::Overflow)?;
+        self.farm.total_staked = self
+            .farm
+            .total_staked
+            .checked_add(amount)
+            .ok_or(AmmDexError::Overflow)?;
+        self.stake_position.reset_debt(self.farm.reward_per_share)?;
+
+        Ok(Staked {
+            pool_config: self.pool_config.key(),
+            user: self.user.key(),
+            amount,
+            total_staked: self.farm.total_staked,
+        })
+    }
+
+    // Rewards earned so far stay pending until claimed
+    pub fn handle_unstake(&mut self, amount: u64, bump: &StakeBumps) -> Result<Unstaked> {
+        if amount == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+        self.open_position(bump);
+        if amount > self.stake_position.amount {
+            return Err(AmmDexError::InsufficientStake.into());
+        }
+        self.checkpoint()?;
+
+        self.stake_position.amount -= amount;
+        self.farm.total_staked -= amount;
+        self.stake_position.reset_debt(self.farm.reward_per_share)?;
+
+        self.transfer_from_farm(
+            self.farm_lp_vault.to_account_info(),
+            self.lp_token.to_account_info(),
+            self.user_lp_token_ac.to_account_info(),
+            self.token_program.to_account_info(),
+            amount,
+            self.lp_token.decimals,
+        )?;
+
+        Ok(Unstaked {
+            pool_config: self.pool_config.key(),
+            user: self.user.key(),
+            amount,
+            total_staked: self.farm.total_staked,
+        })
+    }
+
+    pub fn handle_claim_rewards(&mut self, bump: &StakeBumps) -> Result<RewardsClaimed> {
+        self.open_position(bump);
+        self.checkpoint()?;
+
+        let rewards = self.stake_position.pending_rewards;
+        if rewards == 0 {
+            return Err(AmmDexError::NothingToClaim.into());
+        }
+        self.stake_position.pending_rewards = 0;
+
+        self.transfer_from_farm(
+            self.reward_vault.to_account_info(),
+            self.reward_mint.to_account_info(),
+            self.user_reward_token.to_account_info(),
+            self.reward_token_program.to_account_info(),
+            rewards,
+            self.reward_mint.decimals,
+        )?;
+
+        Ok(RewardsClaimed {
+            pool_config: self.pool_config.key(),
+            user: self.user.key(),
+            amount: rewards,
+        })
+    }
+
+    // Fills in a position created by init_if_needed
+    fn open_position(&mut self, bump: &StakeBumps) {
+        if self.stake_position.owner == Pubkey::default() {
+            self.stake_position.set_inner(StakePosition {
+                farm: self.farm.key(),
+                owner: self.user.key(),
+                amount: 0,
+                reward_debt: 0,
+                pending_rewards: 0,
+                bump: bump.stake_position,
+            });
+        }
+    }
+
+    // Brings the farm up to now and books what the position earned at its old size
+    fn checkpoint(&mut self) -> Result<()> {
+        self.farm.accrue(Clock::get()?.unix_timestamp)?;
+        self.stake_position.settle(self.farm.reward_per_share)?;
+        self.stake_position.reset_debt(self.farm.reward_per_share)
+    }
+
+    fn transfer_from_farm(
+        &self,
+        from: AccountInfo<'info>,
+        mint: AccountInfo<'info>,
+        to: AccountInfo<'info>,
+        token_program: AccountInfo<'info>,
+        amount: u64,
+        decimals: u8,
+    ) -> Result<()> {
+        let pool_config = self.pool_config.key();
+        let seeds = &[b"farm", pool_config.as_ref(), &[self.farm.bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from,
+            mint,
+            to,
+            authority: self.farm.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(token_program, cpi_accounts, signer_seeds);
+        transfer_checked(cpi_ctx, amount, decimals)
+    }
+}
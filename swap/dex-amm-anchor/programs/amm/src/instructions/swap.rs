@@ -1,34 +1,251 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{Mint, Token, TokenAccount,transfer},
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
 };
-use constant_product_curve::{ConstantProduct, LiquidityPair};
 
-use crate::{error::PoolConfigError, state::PoolConfig};
+use crate::{
+    deadline::check_deadline,
+    curve::{amount_in_for_exact_out, amount_out_for_exact_in, gross_up},
+    error::{AmmDexError, PoolConfigError},
+    events::Swapped,
+    oracle,
+    state::PoolConfig,
+    transfer_fee::amount_before_transfer_fee,
+};
+
+pub struct SwapQuote {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub lp_fee: u16,
+    pub protocol_fee: u64,
+}
+
+// Exact-in pricing shared by every instruction that trades against a pool.
+// Slippage is left to the caller so a zero quote gets its own error.
+pub fn quote_exact_in(
+    pool_config: &PoolConfig,
+    lp_supply: u64,
+    lp_pair_x: bool,
+    amount: u64,
+) -> Result<SwapQuote> {
+    // Set aside the protocol share of the input before it reaches the curve
+    let protocol_fee = pool_config.protocol_fee_amount(amount);
+    let (reserve_in, reserve_out) = if lp_pair_x {
+        (pool_config.reserve_x, pool_config.reserve_y)
+    } else {
+        (pool_config.reserve_y, pool_config.reserve_x)
+    };
+    // Calculate Swap Amounts
+    let lp_fee = pool_config.swap_fee(Clock::get()?.unix_timestamp);
+    let amount_out = amount_out_for_exact_in(
+        pool_config,
+        lp_fee,
+        reserve_in,
+        reserve_out,
+        lp_supply,
+        amount - protocol_fee,
+    )?;
+    if amount_out == 0 {
+        return Err(AmmDexError::ZeroOutput.into());
+    }
+    Ok(SwapQuote {
+        amount_in: amount,
+        amount_out,
+        lp_fee,
+        protocol_fee,
+    })
+}
+
+// Exact-out pricing: what has to reach the input vault to take `amount_out`, worked
+// back through the LP and protocol fees. Each step rounds up so the pool always comes
+// out ahead. Shared with quote_swap_exact_out.
+pub fn quote_exact_out(
+    pool_config: &PoolConfig,
+    lp_pair_x: bool,
+    amount_out: u64,
+) -> Result<SwapQuote> {
+    let (reserve_in, reserve_out) = if lp_pair_x {
+        (pool_config.reserve_x, pool_config.reserve_y)
+    } else {
+        (pool_config.reserve_y, pool_config.reserve_x)
+    };
+    let lp_fee = pool_config.swap_fee(Clock::get()?.unix_timestamp);
+    let curve_in =
+        amount_in_for_exact_out(pool_config, lp_fee, reserve_in, reserve_out, amount_out)?;
+    let amount_in = gross_up(curve_in as u128, pool_config.protocol_fee)?;
+    Ok(SwapQuote {
+        amount_in,
+        amount_out,
+        lp_fee,
+        protocol_fee: pool_config.protocol_fee_amount(amount_in),
+    })
+}
+
+// The accounts one exact-in trade moves tokens between, borrowed from whichever
+// instruction is running it
+pub struct SwapAccounts<'a, 'info> {
+    pub user: &'a Signer<'info>,
+    pub pool_config: &'a mut Account<'info, PoolConfig>,
+    pub lp_supply: u64,
+    pub mint_in: &'a InterfaceAccount<'info, Mint>,
+    pub user_in: &'a InterfaceAccount<'info, TokenAccount>,
+    pub vault_in: &'a mut InterfaceAccount<'info, TokenAccount>,
+    pub token_program_in: AccountInfo<'info>,
+    pub mint_out: &'a InterfaceAccount<'info, Mint>,
+    pub vault_out: &'a InterfaceAccount<'info, TokenAccount>,
+    pub user_out: &'a InterfaceAccount<'info, TokenAccount>,
+    pub token_program_out: AccountInfo<'info>,
+}
+
+// Runs an exact-in trade end to end: gating, pricing what actually reached the vault,
+// paying out, booking the reserves and updating the oracle. Shared by swap and every
+// leg of route_swap; slippage is left to the caller.
+pub fn execute_swap(accounts: SwapAccounts, lp_pair_x: bool, amount: u64) -> Result<Swapped> {
+    let SwapAccounts {
+        user,
+        pool_config,
+        lp_supply,
+        mint_in,
+        user_in,
+        vault_in,
+        token_program_in,
+        mint_out,
+        vault_out,
+        user_out,
+        token_program_out,
+    } = accounts;
+    pool_config.check_swap_allowed()?;
+    // Price what actually reached the vault, not what the user asked to send
+    let received = transfer_to_vault(user, user_in, vault_in, mint_in, token_program_in, amount)?;
+    let quote = quote_exact_in(pool_config, lp_supply, lp_pair_x, received)?;
+    transfer_from_vault(
+        pool_config,
+        vault_out,
+        user_out,
+        mint_out,
+        token_program_out,
+        quote.amount_out,
+    )?;
+    pool_config.apply_swap(lp_pair_x, received, quote.amount_out, quote.protocol_fee)?;
+    oracle::update(pool_config, Clock::get()?.unix_timestamp)?;
+
+    Ok(Swapped {
+        pool_config: pool_config.key(),
+        user: user.key(),
+        lp_pair_x,
+        amount_in: received,
+        amount_out: quote.amount_out,
+        lp_fee: quote.lp_fee,
+        protocol_fee: quote.protocol_fee,
+        reserve_x: pool_config.reserve_x,
+        reserve_y: pool_config.reserve_y,
+    })
+}
 
+// Moves the input into its vault and returns how much actually arrived
+fn transfer_to_vault<'info>(
+    user: &Signer<'info>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: AccountInfo<'info>,
+    amount: u64,
+) -> Result<u64> {
+    let before = to.amount;
+    let cpi_accounts = TransferChecked {
+        from: from.to_account_info(),
+        mint: mint.to_account_info(),
+        to: to.to_account_info(),
+        authority: user.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new(token_program, cpi_accounts);
+    transfer_checked(cpi_ctx, amount, mint.decimals)?;
+
+    to.reload()?;
+    Ok(to.amount - before)
+}
+
+// Pays the output out of a vault, signed by the pool
+fn transfer_from_vault<'info>(
+    pool_config: &Account<'info, PoolConfig>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let pool_seeds = pool_config.seeds.to_le_bytes();
+    let seeds = &[
+        b"pool-config".as_ref(),
+        pool_config.token_x_mint.as_ref(),
+        pool_config.token_y_mint.as_ref(),
+        pool_seeds.as_ref(),
+        &[pool_config.pool_config_bump],
+    ];
+    let signer = &[&seeds[..]];
+    let cpi_accounts = TransferChecked {
+        from: from.to_account_info(),
+        mint: mint.to_account_info(),
+        to: to.to_account_info(),
+        authority: pool_config.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program, cpi_accounts, signer);
+    transfer_checked(cpi_ctx, amount, mint.decimals)?;
+    Ok(())
+}
+
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Swap<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
 
-    pub token_x_mint: Account<'info, Mint>,
-    pub token_y_mint: Account<'info, Mint>,
+    #[account(mint::token_program = token_x_program)]
+    pub token_x_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_y_program)]
+    pub token_y_mint: InterfaceAccount<'info, Mint>,
 
-    #[account(associated_token::mint = token_x_mint, associated_token::authority = user)]
-    pub user_x_token: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_x_program,
+    )]
+    pub user_x_token: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(associated_token::mint = token_y_mint, associated_token::authority = user)]
-    pub user_y_token: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_y_program,
+    )]
+    pub user_y_token: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(associated_token::mint = token_x_mint, associated_token::authority = pool_config)]
-    pub token_x_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_x_program,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(associated_token::mint = token_y_mint, associated_token::authority = pool_config)]
-    pub token_y_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_y_program,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        seeds = [b"pool-config", pool_config.owner.as_ref().unwrap().as_ref()],
+        mut,
+        seeds = [
+            b"pool-config",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
         bump = pool_config.pool_config_bump,
         has_one = token_x_mint,
         has_one = token_y_mint,
@@ -36,92 +253,137 @@ pub struct Swap<'info> {
     pub pool_config: Account<'info, PoolConfig>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_x_program: Interface<'info, TokenInterface>,
+    pub token_y_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 
-    pub lp_token: Account<'info, Mint>,
+    #[account(
+        seeds = [b"lp-token", pool_config.key().as_ref()],
+        bump = pool_config.lp_bump,
+    )]
+    pub lp_token: InterfaceAccount<'info, Mint>,
 
     #[account(
         init_if_needed,
         payer = user,
         associated_token::mint = lp_token,
         associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub user_lp_token_ac: Account<'info, TokenAccount>,
+    pub user_lp_token_ac: InterfaceAccount<'info, TokenAccount>,
 }
 
 impl<'info> Swap<'info> {
-    pub fn handle_swap(&mut self, lp_pair_x: bool, amount: u64, min_swap_amount: u64) -> Result<()> {
+    pub fn handle_swap(
+        &mut self,
+        lp_pair_x: bool,
+        amount: u64,
+        min_swap_amount: u64,
+        deadline: Option<i64>,
+    ) -> Result<Swapped> {
         // Amount Checks & other checks
-        if self.pool_config.owner.is_none() {
-            return Err(PoolConfigError::PoolNotInitialized.into());
-        }
+        check_deadline(deadline)?;
         if amount == 0 || min_swap_amount == 0 {
             return Err(PoolConfigError::InvalidAmount.into());
         }
-        // Set Liquidity Pair Direction
-        let direction = match lp_pair_x {
-            true => LiquidityPair::X,
-            false => LiquidityPair::Y,
-        };
-        // Initialize Curve
-        let mut curve = ConstantProduct::init(
-            self.token_x_vault.amount,
-            self.token_y_vault.amount,
-            self.lp_token.supply,
-            self.pool_config.lp_fee,
-            None,
-        ).unwrap();
-        // Calculate Swap Amounts
-        let swap_result = curve.swap(direction, amount, min_swap_amount).unwrap();
-        self.deposit_to_vault(
-            if lp_pair_x { &self.user_x_token } else { &self.user_y_token },
-            if lp_pair_x { &self.token_x_vault } else { &self.token_y_vault },
-            swap_result.deposit,
+        let swapped = execute_swap(self.swap_accounts(lp_pair_x), lp_pair_x, amount)?;
+        if swapped.amount_out < min_swap_amount {
+            return Err(AmmDexError::SlippageToleranceExceeded.into());
+        }
+
+        Ok(swapped)
+    }
+
+    pub fn handle_swap_exact_out(
+        &mut self,
+        lp_pair_x: bool,
+        amount_out: u64,
+        max_amount_in: u64,
+        deadline: Option<i64>,
+    ) -> Result<Swapped> {
+        // Amount Checks & other checks
+        check_deadline(deadline)?;
+        self.pool_config.check_swap_allowed()?;
+        if amount_out == 0 || max_amount_in == 0 {
+            return Err(PoolConfigError::InvalidAmount.into());
+        }
+        let user = self.user.key();
+        let accounts = self.swap_accounts(lp_pair_x);
+        // The input mint's transfer fee goes on top of the pool's fees, also rounded up
+        let quote = quote_exact_out(accounts.pool_config, lp_pair_x, amount_out)?;
+        let vault_in = quote.amount_in;
+        let amount_in = amount_before_transfer_fee(accounts.mint_in, vault_in)?;
+        if amount_in > max_amount_in {
+            return Err(AmmDexError::SlippageToleranceExceeded.into());
+        }
+        let received = transfer_to_vault(
+            accounts.user,
+            accounts.user_in,
+            accounts.vault_in,
+            accounts.mint_in,
+            accounts.token_program_in,
+            amount_in,
         )?;
-        self.withdraw_from_vault(
-            if lp_pair_x { &self.token_y_vault } else { &self.token_x_vault },
-            if lp_pair_x { &self.user_y_token } else { &self.user_x_token },
-            swap_result.withdraw,
+        if received < vault_in {
+            return Err(AmmDexError::InsufficientBalance.into());
+        }
+        transfer_from_vault(
+            accounts.pool_config,
+            accounts.vault_out,
+            accounts.user_out,
+            accounts.mint_out,
+            accounts.token_program_out,
+            amount_out,
         )?;
-        Ok(())
-    }
-    fn deposit_to_vault(
-        &self,
-        from: &Account<'info, TokenAccount>,
-        to: &Account<'info, TokenAccount>,
-        amount: u64,
-    ) -> Result<()> {
-        let cpi_accounts = anchor_spl::token::Transfer {
-            from: from.to_account_info(),
-            to: to.to_account_info(),
-            authority: self.user.to_account_info(),
-        };
-        let cpi_program = self.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        transfer(cpi_ctx, amount)?;
-        Ok(())
+        let pool_config = accounts.pool_config;
+        let protocol_fee = pool_config.protocol_fee_amount(received);
+        pool_config.apply_swap(lp_pair_x, received, amount_out, protocol_fee)?;
+        oracle::update(pool_config, Clock::get()?.unix_timestamp)?;
+
+        Ok(Swapped {
+            pool_config: pool_config.key(),
+            user,
+            lp_pair_x,
+            amount_in: received,
+            amount_out,
+            lp_fee: quote.lp_fee,
+            protocol_fee,
+            reserve_x: pool_config.reserve_x,
+            reserve_y: pool_config.reserve_y,
+        })
     }
-    fn withdraw_from_vault(
-        &self,
-        from: &Account<'info, TokenAccount>,
-        to: &Account<'info, TokenAccount>,
-        amount: u64,
-    ) -> Result<()> {
-        let seeds = &[
-            b"pool-config",
-            self.pool_config.owner.as_ref().unwrap().as_ref(),
-            &[self.pool_config.pool_config_bump],
-        ];
-        let signer = &[&seeds[..]];
-        let cpi_accounts = anchor_spl::token::Transfer {
-            from: from.to_account_info(),
-            to: to.to_account_info(),
-            authority: self.pool_config.to_account_info(),
-        };
-        let cpi_program = self.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-        transfer(cpi_ctx, amount)?;
-        Ok(())
+
+    fn swap_accounts(&mut self, lp_pair_x: bool) -> SwapAccounts<'_, 'info> {
+        let lp_supply = self.lp_token.supply;
+        if lp_pair_x {
+            SwapAccounts {
+                user: &self.user,
+                pool_config: &mut self.pool_config,
+                lp_supply,
+                mint_in: &self.token_x_mint,
+                user_in: &self.user_x_token,
+                vault_in: &mut self.token_x_vault,
+                token_program_in: self.token_x_program.to_account_info(),
+                mint_out: &self.token_y_mint,
+                vault_out: &self.token_y_vault,
+                user_out: &self.user_y_token,
+                token_program_out: self.token_y_program.to_account_info(),
+            }
+        } else {
+            SwapAccounts {
+                user: &self.user,
+                pool_config: &mut self.pool_config,
+                lp_supply,
+                mint_in: &self.token_y_mint,
+                user_in: &self.user_y_token,
+                vault_in: &mut self.token_y_vault,
+                token_program_in: self.token_y_program.to_account_info(),
+                mint_out: &self.token_x_mint,
+                vault_out: &self.token_x_vault,
+                user_out: &self.user_x_token,
+                token_program_out: self.token_x_program.to_account_info(),
+            }
+        }
     }
 }
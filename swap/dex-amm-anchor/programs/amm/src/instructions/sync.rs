@@ -0,0 +1,81 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    error::{AmmDexError, PoolConfigError},
+    events::Synced,
+    oracle,
+    state::PoolConfig,
+};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SyncReserves<'info> {
+    #[account(mint::token_program = token_x_program)]
+    pub token_x_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_y_program)]
+    pub token_y_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        associated_token::mint = token_x_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_x_program,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        associated_token::mint = token_y_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_y_program,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"pool-config",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
+        bump = pool_config.pool_config_bump,
+        has_one = token_x_mint,
+        has_one = token_y_mint,
+    )]
+    pub pool_config: Account<'info, PoolConfig>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_x_program: Interface<'info, TokenInterface>,
+    pub token_y_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> SyncReserves<'info> {
+    // Adopts whatever the vaults hold beyond the unclaimed protocol fees as the new
+    // reserves, handing any direct transfers to the LPs. Anyone may call it.
+    pub fn handle_sync(&mut self) -> Result<Synced> {
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        if self.pool_config.active_loan.is_some() {
+            return Err(AmmDexError::FlashLoanActive.into());
+        }
+        self.pool_config.reserve_x = self
+            .token_x_vault
+            .amount
+            .saturating_sub(self.pool_config.protocol_fees_x);
+        self.pool_config.reserve_y = self
+            .token_y_vault
+            .amount
+            .saturating_sub(self.pool_config.protocol_fees_y);
+        oracle::update(&mut self.pool_config, Clock::get()?.unix_timestamp)?;
+
+        Ok(Synced {
+            pool_config: self.pool_config.key(),
+            reserve_x: self.pool_config.reserve_x,
+            reserve_y: self.pool_config.reserve_y,
+        })
+    }
+}
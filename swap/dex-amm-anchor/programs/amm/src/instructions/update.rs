@@ -1,6 +1,15 @@
 use anchor_lang::prelude::*;
-use crate::{error::AmmDexError, state::PoolConfig};
+use crate::{
+    constants::{
+        EMERGENCY_MODE, MAX_AMP, MAX_AMP_CHANGE, MAX_PROTOCOL_FEE, MAX_SWAP_FEE, MIN_AMP,
+        MIN_RAMP_DURATION, PAUSE_FLAGS_MASK,
+    },
+    error::{AmmDexError, PoolConfigError},
+    events::{OwnershipProposed, OwnershipTransferred, PoolUpdated},
+    state::{AmpRamp, ConfigParam, CurveType, PoolConfig},
+};
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Update<'info> {
     #[account(mut)]
@@ -8,22 +17,163 @@ pub struct Update<'info> {
 
     #[account(
         mut,
-        seeds = [b"pool-config", pool_config.owner.as_ref().unwrap().as_ref()],
+        seeds = [
+            b"pool-config",
+            pool_config.token_x_mint.as_ref(),
+            pool_config.token_y_mint.as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
         bump = pool_config.pool_config_bump,
     )]
     pub pool_config: Account<'info, PoolConfig>,
 }
 
 impl<'info> Update<'info> {
-    pub fn handle_update(&mut self, lock: bool) -> Result<()> {
+    pub fn handle_update_config(&mut self, param: ConfigParam) -> Result<PoolUpdated> {
+        match param {
+            ConfigParam::PauseFlags(pause_flags) => {
+                if pause_flags & !PAUSE_FLAGS_MASK != 0 {
+                    return Err(PoolConfigError::InvalidPauseFlags.into());
+                }
+                // Emergency mode belongs to the emergency admin and the other flags to the
+                // pauser; only bits that actually flip need their role. A no-op still needs
+                // the pauser, or anyone could emit PoolUpdated events for the pool.
+                let changed = pause_flags ^ self.pool_config.pause_flags;
+                if changed & EMERGENCY_MODE != 0 {
+                    self.check_role(self.pool_config.emergency_admin)?;
+                }
+                if changed & !EMERGENCY_MODE != 0 || changed == 0 {
+                    self.check_role(self.pool_config.pauser)?;
+                }
+                self.pool_config.pause_flags = pause_flags;
+            }
+            ConfigParam::LpFee(fee) => {
+                self.check_role(self.pool_config.fee_manager)?;
+                if fee > MAX_SWAP_FEE {
+                    return Err(PoolConfigError::FeeTooHigh.into());
+                }
+                self.pool_config.lp_fee = fee;
+            }
+            ConfigParam::ProtocolFee(fee) => {
+                self.check_role(self.pool_config.fee_manager)?;
+                if fee > MAX_PROTOCOL_FEE {
+                    return Err(PoolConfigError::FeeTooHigh.into());
+                }
+                self.pool_config.protocol_fee = fee;
+            }
+            ConfigParam::FeeRecipient(fee_recipient) => {
+                self.check_role(self.pool_config.fee_manager)?;
+                self.pool_config.fee_recipient = fee_recipient;
+            }
+            ConfigParam::AmpRamp(ramp) => {
+                self.check_owner()?;
+                self.ramp_amp(ramp)?;
+            }
+            ConfigParam::DynamicFee(dynamic_fee) => {
+                self.check_role(self.pool_config.fee_manager)?;
+                if let Some(bounds) = dynamic_fee {
+                    if bounds.min_fee > bounds.max_fee || bounds.max_fee > MAX_SWAP_FEE {
+                        return Err(PoolConfigError::InvalidFeeBounds.into());
+                    }
+                }
+                self.pool_config.dynamic_fee = dynamic_fee;
+            }
+            ConfigParam::Pauser(pauser) => {
+                self.check_owner()?;
+                self.pool_config.pauser = pauser;
+            }
+            ConfigParam::FeeManager(fee_manager) => {
+                self.check_owner()?;
+                self.pool_config.fee_manager = fee_manager;
+            }
+            ConfigParam::EmergencyAdmin(emergency_admin) => {
+                self.check_owner()?;
+                self.pool_config.emergency_admin = emergency_admin;
+            }
+        }
+
+        Ok(PoolUpdated {
+            pool_config: self.pool_config.key(),
+            user: self.user.key(),
+            param,
+        })
+    }
+
+    // The current owner keeps control until the proposed key accepts; None withdraws a proposal
+    pub fn handle_propose_owner(&mut self, new_owner: Option<Pubkey>) -> Result<OwnershipProposed> {
+        self.check_owner()?;
+        self.pool_config.pending_owner = new_owner;
+
+        Ok(OwnershipProposed {
+            pool_config: self.pool_config.key(),
+            owner: self.user.key(),
+            pending_owner: new_owner,
+        })
+    }
+
+    pub fn handle_accept_owner(&mut self) -> Result<OwnershipTransferred> {
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        require!(
+            self.pool_config.pending_owner == Some(self.user.key()),
+            AmmDexError::InvalidAuthority
+        );
+        let previous_owner = self.pool_config.owner;
+        self.pool_config.owner = Some(self.user.key());
+        self.pool_config.pending_owner = None;
+
+        Ok(OwnershipTransferred {
+            pool_config: self.pool_config.key(),
+            previous_owner,
+            new_owner: self.user.key(),
+        })
+    }
+
+    fn check_owner(&self) -> Result<()> {
         if self.pool_config.owner.is_none() {
-            return Err(crate::error::PoolConfigError::PoolNotInitialized.into());
+            return Err(PoolConfigError::PoolNotInitialized.into());
         }
         require!(
             self.pool_config.owner == Some(self.user.key()),
             AmmDexError::InvalidAuthority
         );
-        self.pool_config.is_locked = lock;
         Ok(())
     }
-}
\ No newline at end of file
+
+    // Signer must be the owner or the holder of `role`
+    fn check_role(&self, role: Option<Pubkey>) -> Result<()> {
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        let user = Some(self.user.key());
+        require!(
+            self.pool_config.owner == user || role == user,
+            AmmDexError::InvalidAuthority
+        );
+        Ok(())
+    }
+
+    // Starts a new ramp from wherever the current one has got to
+    fn ramp_amp(&mut self, ramp: AmpRamp) -> Result<()> {
+        if self.pool_config.curve_type != CurveType::StableSwap {
+            return Err(PoolConfigError::InvalidAmpRamp.into());
+        }
+        if !(MIN_AMP..=MAX_AMP).contains(&ramp.target_amp) {
+            return Err(PoolConfigError::InvalidAmplification.into());
+        }
+        let now = Clock::get()?.unix_timestamp;
+        if ramp.ramp_end < now + MIN_RAMP_DURATION {
+            return Err(PoolConfigError::InvalidAmpRamp.into());
+        }
+        let current = self.pool_config.current_amp(now);
+        if ramp.target_amp > current * MAX_AMP_CHANGE || ramp.target_amp * MAX_AMP_CHANGE < current {
+            return Err(PoolConfigError::InvalidAmpRamp.into());
+        }
+        self.pool_config.amp_initial = current;
+        self.pool_config.amp_target = ramp.target_amp;
+        self.pool_config.amp_ramp_start = now;
+        self.pool_config.amp_ramp_end = ramp.ramp_end;
+        Ok(())
+    }
+}
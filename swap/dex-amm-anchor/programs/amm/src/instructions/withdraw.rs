@@ -1,36 +1,121 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{burn, transfer, Burn, Mint, Token, TokenAccount, Transfer},
+    token_interface::{
+        burn, transfer_checked, Burn, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
 };
 
 use crate::{
+    deadline::check_deadline,
     error::{AmmDexError, PoolConfigError},
+    events::LiquidityRemoved,
+    instructions::swap::{quote_exact_in, SwapQuote},
+    oracle,
     state::PoolConfig,
 };
 
+// Pro-rata share of the reserves for `lp_amount` LP. Shared with quote_withdraw.
+pub fn withdraw_token_amounts(
+    pool_config: &PoolConfig,
+    lp_supply: u64,
+    lp_amount: u64,
+) -> Result<(u64, u64)> {
+    if lp_supply == 0 {
+        return Err(PoolConfigError::InvalidAmount.into());
+    }
+
+    let (reserve_x, reserve_y) = (pool_config.reserve_x, pool_config.reserve_y);
+
+    let withdraw_x = (reserve_x as u128)
+        .checked_mul(lp_amount as u128)
+        .ok_or(AmmDexError::Overflow)?
+        / lp_supply as u128;
+
+    let withdraw_y = (reserve_y as u128)
+        .checked_mul(lp_amount as u128)
+        .ok_or(AmmDexError::Overflow)?
+        / lp_supply as u128;
+
+    Ok((withdraw_x as u64, withdraw_y as u64))
+}
+
+// Payout of a single-sided withdraw: the wanted side's pro-rata share plus the other
+// side's share sold against what is left. `pool_config` must already have both shares
+// taken out of its reserves. Shared with quote_withdraw_single.
+pub fn withdraw_single_amount(
+    pool_config: &PoolConfig,
+    lp_supply_after: u64,
+    lp_pair_x: bool,
+    (withdraw_x, withdraw_y): (u64, u64),
+) -> Result<(u64, Option<SwapQuote>)> {
+    let (kept, unwanted) = if lp_pair_x {
+        (withdraw_x, withdraw_y)
+    } else {
+        (withdraw_y, withdraw_x)
+    };
+    let quote = if unwanted == 0 {
+        None
+    } else {
+        Some(quote_exact_in(pool_config, lp_supply_after, !lp_pair_x, unwanted)?)
+    };
+    let amount_out = kept
+        .checked_add(quote.as_ref().map_or(0, |quote| quote.amount_out))
+        .ok_or(AmmDexError::Overflow)?;
+    Ok((amount_out, quote))
+}
+
+#[event_cpi]
 #[derive(Accounts)]
 pub struct Withdraw<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
 
-    pub token_x_mint: Account<'info, Mint>,
-    pub token_y_mint: Account<'info, Mint>,
+    #[account(mint::token_program = token_x_program)]
+    pub token_x_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_y_program)]
+    pub token_y_mint: InterfaceAccount<'info, Mint>,
 
-    #[account(associated_token::mint = token_x_mint, associated_token::authority = user)]
-    pub user_x_token: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_x_program,
+    )]
+    pub user_x_token: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(associated_token::mint = token_y_mint, associated_token::authority = user)]
-    pub user_y_token: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_y_program,
+    )]
+    pub user_y_token: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(associated_token::mint = token_x_mint, associated_token::authority = pool_config)]
-    pub token_x_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_x_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_x_program,
+    )]
+    pub token_x_vault: InterfaceAccount<'info, TokenAccount>,
 
-    #[account(associated_token::mint = token_y_mint, associated_token::authority = pool_config)]
-    pub token_y_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = token_y_mint,
+        associated_token::authority = pool_config,
+        associated_token::token_program = token_y_program,
+    )]
+    pub token_y_vault: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        seeds = [b"pool-config", pool_config.owner.as_ref().unwrap().as_ref()],
+        mut,
+        seeds = [
+            b"pool-config",
+            token_x_mint.key().as_ref(),
+            token_y_mint.key().as_ref(),
+            pool_config.seeds.to_le_bytes().as_ref(),
+        ],
         bump = pool_config.pool_config_bump,
         has_one = token_x_mint,
         has_one = token_y_mint,
@@ -38,17 +123,25 @@ pub struct Withdraw<'info> {
     pub pool_config: Account<'info, PoolConfig>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_x_program: Interface<'info, TokenInterface>,
+    pub token_y_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 
-    pub lp_token: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"lp-token", pool_config.key().as_ref()],
+        bump = pool_config.lp_bump,
+    )]
+    pub lp_token: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = lp_token,
         associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub user_lp_token_ac: Account<'info, TokenAccount>,
+    pub user_lp_token_ac: InterfaceAccount<'info, TokenAccount>,
 }
 
 impl<'info> Withdraw<'info> {
@@ -57,78 +150,186 @@ impl<'info> Withdraw<'info> {
         amount: u64,
         min_token_x: u64,
         min_token_y: u64,
-    ) -> Result<()> {
+        deadline: Option<i64>,
+    ) -> Result<LiquidityRemoved> {
         // Amount Checks & other checks
+        check_deadline(deadline)?;
         if self.pool_config.owner.is_none() {
             return Err(PoolConfigError::PoolNotInitialized.into());
         }
+        if self.pool_config.withdrawals_paused() {
+            return Err(PoolConfigError::WithdrawalsPaused.into());
+        }
+        if self.pool_config.active_loan.is_some() {
+            return Err(AmmDexError::FlashLoanActive.into());
+        }
         if amount == 0 {
             return Err(PoolConfigError::InvalidAmount.into());
         }
 
-        let (withdraw_x, withdraw_y) = self.calculate_withdraw_amounts(amount)?;
+        let (withdraw_x, withdraw_y) =
+            withdraw_token_amounts(&self.pool_config, self.lp_token.supply, amount)?;
         if withdraw_x < min_token_x || withdraw_y < min_token_y {
             return Err(AmmDexError::SlippageToleranceExceeded.into());
         }
+        self.remove_liquidity(withdraw_x, withdraw_y)?;
 
         // Withdraw tokens to user
-        self.transfer_from_vault(&self.token_x_vault, &self.user_x_token, withdraw_x)?;
-        self.transfer_from_vault(&self.token_y_vault, &self.user_y_token, withdraw_y)?;
+        self.transfer_from_vault(
+            &self.token_x_vault,
+            &self.user_x_token,
+            &self.token_x_mint,
+            &self.token_x_program,
+            withdraw_x,
+        )?;
+        self.transfer_from_vault(
+            &self.token_y_vault,
+            &self.user_y_token,
+            &self.token_y_mint,
+            &self.token_y_program,
+            withdraw_y,
+        )?;
 
         // Burn LP tokens from user
         self.burn_lp_tokens(amount)?;
 
-        msg!(
-            "Withdraw complete: X = {}, Y = {}, LP burned = {}",
-            withdraw_x,
-            withdraw_y,
-            amount
-        );
+        oracle::update(&mut self.pool_config, Clock::get()?.unix_timestamp)?;
 
-        Ok(())
+        Ok(LiquidityRemoved {
+            pool_config: self.pool_config.key(),
+            user: self.user.key(),
+            amount_x: withdraw_x,
+            amount_y: withdraw_y,
+            lp_amount: amount,
+            lp_fee: 0,
+            protocol_fee: 0,
+            reserve_x: self.pool_config.reserve_x,
+            reserve_y: self.pool_config.reserve_y,
+        })
     }
 
-    fn calculate_withdraw_amounts(&self, lp_amount: u64) -> Result<(u64, u64)> {
-        let supply = self.lp_token.supply;
-        if supply == 0 {
+    // Withdraws into one token: takes the usual pro-rata amounts, then trades the
+    // unwanted side back into the pool. The trade pays the usual LP and protocol fees.
+    pub fn handle_withdraw_single(
+        &mut self,
+        lp_pair_x: bool,
+        amount: u64,
+        min_amount_out: u64,
+        deadline: Option<i64>,
+    ) -> Result<LiquidityRemoved> {
+        // Amount Checks & other checks
+        check_deadline(deadline)?;
+        if self.pool_config.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        if self.pool_config.withdrawals_paused() {
+            return Err(PoolConfigError::WithdrawalsPaused.into());
+        }
+        self.pool_config.check_swap_allowed()?;
+        if amount == 0 || min_amount_out == 0 {
             return Err(PoolConfigError::InvalidAmount.into());
         }
 
-        let withdraw_x = (self.token_x_vault.amount as u128)
-            .checked_mul(lp_amount as u128)
-            .unwrap()
-            / supply as u128;
+        let (withdraw_x, withdraw_y) =
+            withdraw_token_amounts(&self.pool_config, self.lp_token.supply, amount)?;
+        // The unwanted side never leaves its vault; it is sold against what remains
+        self.remove_liquidity(withdraw_x, withdraw_y)?;
+        let (amount_out, quote) = withdraw_single_amount(
+            &self.pool_config,
+            self.lp_token.supply - amount,
+            lp_pair_x,
+            (withdraw_x, withdraw_y),
+        )?;
+        if amount_out < min_amount_out {
+            return Err(AmmDexError::SlippageToleranceExceeded.into());
+        }
+
+        // Withdraw tokens to user
+        if lp_pair_x {
+            self.transfer_from_vault(
+                &self.token_x_vault,
+                &self.user_x_token,
+                &self.token_x_mint,
+                &self.token_x_program,
+                amount_out,
+            )?;
+        } else {
+            self.transfer_from_vault(
+                &self.token_y_vault,
+                &self.user_y_token,
+                &self.token_y_mint,
+                &self.token_y_program,
+                amount_out,
+            )?;
+        }
+        if let Some(quote) = &quote {
+            self.pool_config
+                .apply_swap(!lp_pair_x, quote.amount_in, quote.amount_out, quote.protocol_fee)?;
+        }
+
+        // Burn LP tokens from user
+        self.burn_lp_tokens(amount)?;
 
-        let withdraw_y = (self.token_y_vault.amount as u128)
-            .checked_mul(lp_amount as u128)
-            .unwrap()
-            / supply as u128;
+        oracle::update(&mut self.pool_config, Clock::get()?.unix_timestamp)?;
 
-        Ok((withdraw_x as u64, withdraw_y as u64))
+        let (amount_x, amount_y) = if lp_pair_x { (amount_out, 0) } else { (0, amount_out) };
+        let (lp_fee, protocol_fee) =
+            quote.map_or((0, 0), |quote| (quote.lp_fee, quote.protocol_fee));
+        Ok(LiquidityRemoved {
+            pool_config: self.pool_config.key(),
+            user: self.user.key(),
+            amount_x,
+            amount_y,
+            lp_amount: amount,
+            lp_fee,
+            protocol_fee,
+            reserve_x: self.pool_config.reserve_x,
+            reserve_y: self.pool_config.reserve_y,
+        })
+    }
+
+    fn remove_liquidity(&mut self, withdraw_x: u64, withdraw_y: u64) -> Result<()> {
+        self.pool_config.reserve_x = self
+            .pool_config
+            .reserve_x
+            .checked_sub(withdraw_x)
+            .ok_or(AmmDexError::Underflow)?;
+        self.pool_config.reserve_y = self
+            .pool_config
+            .reserve_y
+            .checked_sub(withdraw_y)
+            .ok_or(AmmDexError::Underflow)?;
+        Ok(())
     }
 
     fn transfer_from_vault(
         &self,
-        from: &Account<'info, TokenAccount>,
-        to: &Account<'info, TokenAccount>,
+        from: &InterfaceAccount<'info, TokenAccount>,
+        to: &InterfaceAccount<'info, TokenAccount>,
+        mint: &InterfaceAccount<'info, Mint>,
+        token_program: &Interface<'info, TokenInterface>,
         amount: u64,
     ) -> Result<()> {
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from: from.to_account_info(),
+            mint: mint.to_account_info(),
             to: to.to_account_info(),
             authority: self.pool_config.to_account_info(),
         };
 
+        let pool_seeds = self.pool_config.seeds.to_le_bytes();
         let seeds = &[
-            b"pool-config",
-            self.pool_config.owner.as_ref().unwrap().as_ref(),
+            b"pool-config".as_ref(),
+            self.pool_config.token_x_mint.as_ref(),
+            self.pool_config.token_y_mint.as_ref(),
+            pool_seeds.as_ref(),
             &[self.pool_config.pool_config_bump],
         ];
         let signer_seeds = &[&seeds[..]];
 
         let cpi_ctx =
-            CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
-        transfer(cpi_ctx, amount)?;
+            CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+        transfer_checked(cpi_ctx, amount, mint.decimals)?;
         Ok(())
     }
 
@@ -4,38 +4,262 @@ use anchor_lang::prelude::*;
 pub mod instructions;
 declare_id!("85krVjvbktge3QdRPU5dRYSaaSXi2CgB7cqhreoABi36");
 pub use instructions::*;
+use state::{ConfigParam, CurveType, Twap};
 pub mod state;
 pub mod constants;
 pub mod error;
+pub mod curve;
+pub mod transfer_fee;
+pub mod oracle;
+pub mod events;
+pub mod deadline;
 
 
 #[program]
 pub mod amm {
     use super::*;
 
-    pub fn initialize(ctx: Context<Init>, seeds:u64, fee: u16, owner: Option<Pubkey>) -> Result<()> {
-        ctx.accounts.handle_initialize(seeds, &ctx.bumps , fee, owner)?;
+    pub fn initialize_factory(ctx: Context<InitFactory>) -> Result<()> {
+        ctx.accounts.handle_initialize_factory(&ctx.bumps)?;
         Ok(())
     }
 
-    pub fn deposit(ctx: Context<Deposit>, amount: u64, max_token_x: u64, max_token_y: u64) -> Result<()> {
-        ctx.accounts.handle_deposit(amount, max_token_x, max_token_y)?;
+    pub fn initialize(
+        ctx: Context<Init>,
+        seeds: u64,
+        fee: u16,
+        owner: Option<Pubkey>,
+        curve_type: CurveType,
+        amp: u64,
+    ) -> Result<()> {
+        let event = ctx.accounts.handle_initialize(seeds, &ctx.bumps, fee, owner, curve_type, amp)?;
+        emit_cpi!(event);
         Ok(())
     }
 
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64, min_token_x: u64, min_token_y: u64) -> Result<()> {
-        ctx.accounts.handle_withdraw(amount, min_token_x, min_token_y)?;
+    pub fn deposit(
+        ctx: Context<Deposit>,
+        amount: u64,
+        max_token_x: u64,
+        max_token_y: u64,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        let event = ctx.accounts.handle_deposit(amount, max_token_x, max_token_y, deadline)?;
+        emit_cpi!(event);
         Ok(())
     }
 
-    pub fn swap(ctx: Context<Swap>, lp_pair_x: bool, amount: u64, min_swap_amount: u64) -> Result<()> {
-        ctx.accounts.handle_swap(lp_pair_x, amount, min_swap_amount)?;
+    pub fn deposit_single(
+        ctx: Context<Deposit>,
+        lp_pair_x: bool,
+        amount: u64,
+        min_lp_out: u64,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        let event = ctx.accounts.handle_deposit_single(lp_pair_x, amount, min_lp_out, deadline)?;
+        emit_cpi!(event);
         Ok(())
     }
 
-    pub fn update(ctx: Context<Update>, locked: bool) -> Result<()> {
-        ctx.accounts.handle_update(locked)?;
+    pub fn withdraw(
+        ctx: Context<Withdraw>,
+        amount: u64,
+        min_token_x: u64,
+        min_token_y: u64,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        let event = ctx.accounts.handle_withdraw(amount, min_token_x, min_token_y, deadline)?;
+        emit_cpi!(event);
         Ok(())
     }
+
+    pub fn withdraw_single(
+        ctx: Context<Withdraw>,
+        lp_pair_x: bool,
+        amount: u64,
+        min_amount_out: u64,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        let event = ctx.accounts.handle_withdraw_single(lp_pair_x, amount, min_amount_out, deadline)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn swap(
+        ctx: Context<Swap>,
+        lp_pair_x: bool,
+        amount: u64,
+        min_swap_amount: u64,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        let event = ctx.accounts.handle_swap(lp_pair_x, amount, min_swap_amount, deadline)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn swap_exact_out(
+        ctx: Context<Swap>,
+        lp_pair_x: bool,
+        amount_out: u64,
+        max_amount_in: u64,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        let event = ctx.accounts.handle_swap_exact_out(lp_pair_x, amount_out, max_amount_in, deadline)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn route_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RouteSwap<'info>>,
+        lp_pair_x: Vec<bool>,
+        amount: u64,
+        min_swap_amount: u64,
+        deadline: Option<i64>,
+    ) -> Result<()> {
+        let events = ctx.accounts.handle_route_swap(
+            ctx.remaining_accounts,
+            lp_pair_x,
+            amount,
+            min_swap_amount,
+            deadline,
+        )?;
+        for event in events {
+            emit_cpi!(event);
+        }
+        Ok(())
+    }
+
+    pub fn update_config(ctx: Context<Update>, param: ConfigParam) -> Result<()> {
+        let event = ctx.accounts.handle_update_config(param)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn propose_owner(ctx: Context<Update>, new_owner: Option<Pubkey>) -> Result<()> {
+        let event = ctx.accounts.handle_propose_owner(new_owner)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn accept_owner(ctx: Context<Update>) -> Result<()> {
+        let event = ctx.accounts.handle_accept_owner()?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn claim_protocol_fees(ctx: Context<ClaimProtocolFees>) -> Result<()> {
+        let event = ctx.accounts.handle_claim_protocol_fees()?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn flash_loan(ctx: Context<FlashLoan>, lp_pair_x: bool, amount: u64) -> Result<()> {
+        let event = ctx.accounts.handle_flash_loan(lp_pair_x, amount)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn flash_repay(ctx: Context<FlashLoan>) -> Result<()> {
+        let event = ctx.accounts.handle_flash_repay()?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn sync(ctx: Context<SyncReserves>) -> Result<()> {
+        let event = ctx.accounts.handle_sync()?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn skim(ctx: Context<Skim>) -> Result<()> {
+        let event = ctx.accounts.handle_skim()?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn close_pool(ctx: Context<ClosePool>) -> Result<()> {
+        let event = ctx.accounts.handle_close_pool(&ctx.bumps)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn create_farm(ctx: Context<CreateFarm>, emission_rate: u64) -> Result<()> {
+        let event = ctx.accounts.handle_create_farm(emission_rate, &ctx.bumps)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn fund_rewards(
+        ctx: Context<FundRewards>,
+        amount: u64,
+        emission_rate: Option<u64>,
+    ) -> Result<()> {
+        let event = ctx.accounts.handle_fund_rewards(amount, emission_rate)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn stake(ctx: Context<Stake>, amount: u64) -> Result<()> {
+        let event = ctx.accounts.handle_stake(amount, &ctx.bumps)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn unstake(ctx: Context<Stake>, amount: u64) -> Result<()> {
+        let event = ctx.accounts.handle_unstake(amount, &ctx.bumps)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn claim_rewards(ctx: Context<Stake>) -> Result<()> {
+        let event = ctx.accounts.handle_claim_rewards(&ctx.bumps)?;
+        emit_cpi!(event);
+        Ok(())
+    }
+
+    pub fn observe(ctx: Context<Observe>, window: u32) -> Result<Twap> {
+        ctx.accounts.handle_observe(window)
+    }
+
+    pub fn quote_swap(ctx: Context<Quote>, lp_pair_x: bool, amount: u64) -> Result<SwapEstimate> {
+        ctx.accounts.handle_quote_swap(lp_pair_x, amount)
+    }
+
+    pub fn quote_deposit(
+        ctx: Context<Quote>,
+        amount: u64,
+        max_token_x: u64,
+        max_token_y: u64,
+    ) -> Result<DepositEstimate> {
+        ctx.accounts.handle_quote_deposit(amount, max_token_x, max_token_y)
+    }
+
+    pub fn quote_withdraw(ctx: Context<Quote>, amount: u64) -> Result<WithdrawEstimate> {
+        ctx.accounts.handle_quote_withdraw(amount)
+    }
+
+    pub fn quote_swap_exact_out(
+        ctx: Context<Quote>,
+        lp_pair_x: bool,
+        amount_out: u64,
+    ) -> Result<SwapExactOutEstimate> {
+        ctx.accounts.handle_quote_swap_exact_out(lp_pair_x, amount_out)
+    }
+
+    pub fn quote_deposit_single(
+        ctx: Context<Quote>,
+        lp_pair_x: bool,
+        amount: u64,
+    ) -> Result<DepositEstimate> {
+        ctx.accounts.handle_quote_deposit_single(lp_pair_x, amount)
+    }
+
+    pub fn quote_withdraw_single(
+        ctx: Context<Quote>,
+        lp_pair_x: bool,
+        amount: u64,
+    ) -> Result<WithdrawEstimate> {
+        ctx.accounts.handle_quote_withdraw_single(lp_pair_x, amount)
+    }
 }
 
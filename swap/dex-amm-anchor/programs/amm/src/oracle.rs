@@ -0,0 +1,63 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::{OBSERVATION_CAPACITY, OBSERVATION_PERIOD},
+    curve::spot_price,
+    error::{AmmDexError, PoolConfigError},
+    state::{Observation, PoolConfig, Twap},
+};
+
+// Cumulative-price oracle in the style of Uniswap v2: each side's spot price is
+// integrated over time, and a TWAP is the difference of two readings divided by
+// the time between them. Prices only change when an instruction moves the reserves,
+// so a single trade cannot shift the average by more than its share of the window.
+
+// Closes the period since the last update at the old price, then records the price
+// left behind by the instruction that just moved the reserves
+pub fn update(pool_config: &mut PoolConfig, now: i64) -> Result<()> {
+    let (price_x_cumulative, price_y_cumulative) = pool_config.cumulative_prices(now);
+    let (reserve_x, reserve_y) = (pool_config.reserve_x, pool_config.reserve_y);
+    let price_before = pool_config.last_price_x;
+    pool_config.price_x_cumulative = price_x_cumulative;
+    pool_config.price_y_cumulative = price_y_cumulative;
+    pool_config.last_price_x = spot_price(pool_config, reserve_x, reserve_y, now)?;
+    pool_config.last_price_y = spot_price(pool_config, reserve_y, reserve_x, now)?;
+    pool_config.oracle_updated_at = now;
+    // The same move drives the dynamic fee
+    pool_config.record_price_move(price_before, pool_config.last_price_x, now);
+
+    let latest = pool_config.observations[pool_config.observation_index as usize];
+    if now - latest.timestamp >= OBSERVATION_PERIOD {
+        let index = (pool_config.observation_index as usize + 1) % OBSERVATION_CAPACITY;
+        pool_config.observations[index] = Observation {
+            timestamp: now,
+            price_x_cumulative,
+            price_y_cumulative,
+        };
+        pool_config.observation_index = index as u8;
+    }
+    Ok(())
+}
+
+// Average price over at least the last `window` seconds, measured from the newest
+// observation old enough to cover it
+pub fn observe(pool_config: &PoolConfig, window: u32, now: i64) -> Result<Twap> {
+    if window == 0 {
+        return Err(PoolConfigError::InvalidAmount.into());
+    }
+    let start = now - window as i64;
+    let observation = pool_config
+        .observations
+        .iter()
+        .filter(|observation| observation.timestamp != 0 && observation.timestamp <= start)
+        .max_by_key(|observation| observation.timestamp)
+        .ok_or(AmmDexError::OracleWindowUnavailable)?;
+
+    let (price_x_cumulative, price_y_cumulative) = pool_config.cumulative_prices(now);
+    let elapsed = now - observation.timestamp;
+    Ok(Twap {
+        price_x: price_x_cumulative.wrapping_sub(observation.price_x_cumulative) / elapsed as u128,
+        price_y: price_y_cumulative.wrapping_sub(observation.price_y_cumulative) / elapsed as u128,
+        window: elapsed,
+    })
+}
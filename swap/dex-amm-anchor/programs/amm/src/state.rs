@@ -1,10 +1,105 @@
 use anchor_lang::prelude::*;
 
+use crate::{
+    constants::{
+        EMERGENCY_MODE, OBSERVATION_CAPACITY, PAUSE_DEPOSIT, PAUSE_SWAP, PAUSE_WITHDRAW,
+        REWARD_PRECISION, VOLATILITY_DECAY_PERIOD, VOLATILITY_PER_FEE_BPS,
+    },
+    error::{AmmDexError, PoolConfigError},
+};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum CurveType {
+    ConstantProduct,
+    StableSwap,
+}
+
+// Linear move of the StableSwap amplification towards `target_amp`, finishing at `ramp_end`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct AmpRamp {
+    pub target_amp: u64,
+    pub ramp_end: i64,
+}
+
+// Owner-set bounds for the volatility-driven LP fee, in basis points
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, InitSpace)]
+pub struct DynamicFee {
+    pub min_fee: u16,
+    pub max_fee: u16,
+}
+
+// One owner-controlled setting changed by `update_config`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub enum ConfigParam {
+    PauseFlags(u8),
+    LpFee(u16),
+    ProtocolFee(u16),
+    // None leaves protocol fees accruing in the vaults until a recipient is set
+    FeeRecipient(Option<Pubkey>),
+    AmpRamp(AmpRamp),
+    // None goes back to the flat lp_fee
+    DynamicFee(Option<DynamicFee>),
+    // Role assignments, owner only; None leaves the role to the owner alone
+    Pauser(Option<Pubkey>),
+    FeeManager(Option<Pubkey>),
+    EmergencyAdmin(Option<Pubkey>),
+}
+
+// Oracle accumulator snapshot, stored every OBSERVATION_PERIOD at most
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, InitSpace)]
+pub struct Observation {
+    pub timestamp: i64,
+    pub price_x_cumulative: u128,
+    pub price_y_cumulative: u128,
+}
+
+// Time-weighted prices returned by `observe`, as Q64.64 fixed point.
+// `window` is the span actually averaged, which is at least the one requested.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct Twap {
+    pub price_x: u128,
+    pub price_y: u128,
+    pub window: i64,
+}
+
+// Flash loan open on a pool until the matching `flash_repay` runs.
+// `vault_balance` is the lent vault before the loan went out.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, InitSpace)]
+pub struct ActiveLoan {
+    pub lp_pair_x: bool,
+    pub vault_balance: u64,
+    pub fee: u64,
+}
+
+// Program-wide pool registry, created once by `initialize_factory`
+#[account]
+#[derive(InitSpace)]
+pub struct Factory {
+    pub pool_count: u64,
+    pub bump: u8,
+}
+
+// Lookup for the canonical pool of a pair at a fee tier, found at
+// ["pool-registration", token_x_mint, token_y_mint, fee_tier]
+#[account]
+#[derive(InitSpace)]
+pub struct PoolRegistration {
+    pub pool_config: Pubkey,
+    pub token_x_mint: Pubkey,
+    pub token_y_mint: Pubkey,
+    pub fee_tier: u16,
+    // Order the pool was registered in, counting from zero
+    pub index: u64,
+    pub bump: u8,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct PoolConfig {
     pub seeds: u64,
     pub lp_fee: u16,
+    // LP fee the pool launched with; keys its registration even after lp_fee changes
+    pub fee_tier: u16,
     pub protocol_fee: u16,
     pub lp_bump: u8,
     pub pool_config_bump: u8,
@@ -12,5 +107,242 @@ pub struct PoolConfig {
     pub token_x_mint: Pubkey,
     pub token_y_mint: Pubkey,
     pub owner: Option<Pubkey>,
-    pub is_locked: bool,
-}
\ No newline at end of file
+    // Proposed by the owner; takes over only once it signs accept_owner
+    pub pending_owner: Option<Pubkey>,
+    // Delegated admin roles; the owner can always act in any of them
+    pub pauser: Option<Pubkey>,
+    pub fee_manager: Option<Pubkey>,
+    pub emergency_admin: Option<Pubkey>,
+    pub fee_recipient: Option<Pubkey>,
+    // Liquidity the curve prices against. Vaults also hold the unclaimed protocol
+    // fees and anything sent to them directly, which only `sync` or `skim` act on.
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    pub protocol_fees_x: u64,
+    pub protocol_fees_y: u64,
+    pub pause_flags: u8,
+    pub curve_type: CurveType,
+    pub amp_initial: u64,
+    pub amp_target: u64,
+    pub amp_ramp_start: i64,
+    pub amp_ramp_end: i64,
+    pub price_x_cumulative: u128,
+    pub price_y_cumulative: u128,
+    pub last_price_x: u128,
+    pub last_price_y: u128,
+    pub oracle_updated_at: i64,
+    pub observation_index: u8,
+    pub observations: [Observation; OBSERVATION_CAPACITY],
+    pub active_loan: Option<ActiveLoan>,
+    // When set, swaps charge a fee within these bounds instead of `lp_fee`
+    pub dynamic_fee: Option<DynamicFee>,
+    pub volatility: u64,
+    pub volatility_updated_at: i64,
+}
+
+impl PoolConfig {
+    // Protocol share of a swap input, in basis points of the amount in.
+    pub fn protocol_fee_amount(&self, amount: u64) -> u64 {
+        (amount as u128 * self.protocol_fee as u128 / 10_000) as u64
+    }
+
+    pub fn deposits_paused(&self) -> bool {
+        self.pause_flags & (PAUSE_DEPOSIT | EMERGENCY_MODE) != 0
+    }
+
+    pub fn swaps_paused(&self) -> bool {
+        self.pause_flags & (PAUSE_SWAP | EMERGENCY_MODE) != 0
+    }
+
+    // Gating shared by every path that trades against the pool, quotes included
+    pub fn check_swap_allowed(&self) -> Result<()> {
+        if self.owner.is_none() {
+            return Err(PoolConfigError::PoolNotInitialized.into());
+        }
+        if self.swaps_paused() {
+            return Err(PoolConfigError::SwapsPaused.into());
+        }
+        // Vaults are short while a flash loan is out, so nothing may price off them
+        if self.active_loan.is_some() {
+            return Err(AmmDexError::FlashLoanActive.into());
+        }
+        Ok(())
+    }
+
+    pub fn withdrawals_paused(&self) -> bool {
+        self.pause_flags & EMERGENCY_MODE == 0 && self.pause_flags & PAUSE_WITHDRAW != 0
+    }
+
+    // Amplification at `now`, interpolated along the current ramp
+    pub fn current_amp(&self, now: i64) -> u64 {
+        if now >= self.amp_ramp_end || self.amp_ramp_end <= self.amp_ramp_start {
+            return self.amp_target;
+        }
+        if now <= self.amp_ramp_start {
+            return self.amp_initial;
+        }
+        let elapsed = (now - self.amp_ramp_start) as u128;
+        let duration = (self.amp_ramp_end - self.amp_ramp_start) as u128;
+        if self.amp_target >= self.amp_initial {
+            let delta = (self.amp_target - self.amp_initial) as u128 * elapsed / duration;
+            self.amp_initial + delta as u64
+        } else {
+            let delta = (self.amp_initial - self.amp_target) as u128 * elapsed / duration;
+            self.amp_initial - delta as u64
+        }
+    }
+
+    // Recorded price movement in basis points, faded linearly to `now`
+    pub fn volatility_at(&self, now: i64) -> u64 {
+        let elapsed = now
+            .saturating_sub(self.volatility_updated_at)
+            .clamp(0, VOLATILITY_DECAY_PERIOD);
+        (self.volatility as u128 * (VOLATILITY_DECAY_PERIOD - elapsed) as u128
+            / VOLATILITY_DECAY_PERIOD as u128) as u64
+    }
+
+    // LP fee for a trade at `now`: `lp_fee`, or the dynamic minimum raised by recent volatility
+    pub fn swap_fee(&self, now: i64) -> u16 {
+        match self.dynamic_fee {
+            None => self.lp_fee,
+            Some(bounds) => {
+                let rise = self.volatility_at(now) / VOLATILITY_PER_FEE_BPS;
+                (bounds.min_fee as u64)
+                    .saturating_add(rise)
+                    .min(bounds.max_fee as u64) as u16
+            }
+        }
+    }
+
+    // Adds a spot price move to the faded accumulator
+    pub fn record_price_move(&mut self, price_before: u128, price_after: u128, now: i64) {
+        // An unpriced pool (first deposit) counts as no move
+        let moved = price_before
+            .abs_diff(price_after)
+            .saturating_mul(10_000)
+            .checked_div(price_before)
+            .unwrap_or(0);
+        self.volatility = self
+            .volatility_at(now)
+            .saturating_add(u64::try_from(moved).unwrap_or(u64::MAX));
+        self.volatility_updated_at = now;
+    }
+
+    // Books a trade: the input less the protocol fee joins the reserves, the output
+    // leaves them, and the protocol fee is set aside for claiming.
+    pub fn apply_swap(
+        &mut self,
+        lp_pair_x: bool,
+        amount_in: u64,
+        amount_out: u64,
+        protocol_fee: u64,
+    ) -> Result<()> {
+        let (reserve_in, reserve_out, protocol_fees) = if lp_pair_x {
+            (&mut self.reserve_x, &mut self.reserve_y, &mut self.protocol_fees_x)
+        } else {
+            (&mut self.reserve_y, &mut self.reserve_x, &mut self.protocol_fees_y)
+        };
+        *reserve_in = amount_in
+            .checked_sub(protocol_fee)
+            .and_then(|net| reserve_in.checked_add(net))
+            .ok_or(AmmDexError::Overflow)?;
+        *reserve_out = reserve_out
+            .checked_sub(amount_out)
+            .ok_or(AmmDexError::InsufficientBalance)?;
+        *protocol_fees = protocol_fees
+            .checked_add(protocol_fee)
+            .ok_or(AmmDexError::Overflow)?;
+        Ok(())
+    }
+
+    // Accumulators extended to `now` at the price left by the last update.
+    // They wrap on overflow; differences between two readings stay exact.
+    pub fn cumulative_prices(&self, now: i64) -> (u128, u128) {
+        let elapsed = now.saturating_sub(self.oracle_updated_at).max(0) as u128;
+        (
+            self.price_x_cumulative
+                .wrapping_add(self.last_price_x.wrapping_mul(elapsed)),
+            self.price_y_cumulative
+                .wrapping_add(self.last_price_y.wrapping_mul(elapsed)),
+        )
+    }
+}
+
+// LP staking farm for one pool, at ["farm", pool_config]. Stakers share `emission_rate`
+// reward tokens per second pro-rata, tracked by `reward_per_share`, scaled by
+// REWARD_PRECISION. Emissions stop when the funded rewards run out.
+#[account]
+#[derive(InitSpace)]
+pub struct Farm {
+    pub pool_config: Pubkey,
+    pub reward_mint: Pubkey,
+    pub emission_rate: u64,
+    pub reward_per_share: u128,
+    pub last_update: i64,
+    pub total_staked: u64,
+    // Funded but not yet emitted
+    pub rewards_remaining: u64,
+    pub bump: u8,
+}
+
+impl Farm {
+    // Emits the rewards due since `last_update` into the accumulator. Nothing is
+    // emitted while nobody is staked, so those rewards stay in the farm.
+    pub fn accrue(&mut self, now: i64) -> Result<()> {
+        let elapsed = now.saturating_sub(self.last_update).max(0) as u64;
+        self.last_update = now;
+        if self.total_staked == 0 {
+            return Ok(());
+        }
+        let emitted = elapsed
+            .saturating_mul(self.emission_rate)
+            .min(self.rewards_remaining);
+        self.rewards_remaining -= emitted;
+        self.reward_per_share = self
+            .reward_per_share
+            .checked_add(emitted as u128 * REWARD_PRECISION / self.total_staked as u128)
+            .ok_or(AmmDexError::Overflow)?;
+        Ok(())
+    }
+}
+
+// A user's stake in a farm, at ["stake-position", farm, owner]
+#[account]
+#[derive(InitSpace)]
+pub struct StakePosition {
+    pub farm: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    // reward_per_share already accounted for at `amount`
+    pub reward_debt: u128,
+    // Earned but not yet claimed
+    pub pending_rewards: u64,
+    pub bump: u8,
+}
+
+impl StakePosition {
+    // Moves what the position earned since it last changed into `pending_rewards`.
+    // Call after `Farm::accrue` and before changing `amount`.
+    pub fn settle(&mut self, reward_per_share: u128) -> Result<()> {
+        let earned = u64::try_from(self.accumulated(reward_per_share)? - self.reward_debt)
+            .map_err(|_| AmmDexError::Overflow)?;
+        self.pending_rewards = self
+            .pending_rewards
+            .checked_add(earned)
+            .ok_or(AmmDexError::Overflow)?;
+        Ok(())
+    }
+
+    // Rebases the debt once `amount` has changed
+    pub fn reset_debt(&mut self, reward_per_share: u128) -> Result<()> {
+        self.reward_debt = self.accumulated(reward_per_share)?;
+        Ok(())
+    }
+
+    fn accumulated(&self, reward_per_share: u128) -> Result<u128> {
+        Ok((self.amount as u128)
+            .checked_mul(reward_per_share)
+            .ok_or(AmmDexError::Overflow)?
+            / REWARD_PRECISION)
+    }
+}
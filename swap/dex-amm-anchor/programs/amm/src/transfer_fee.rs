@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022::spl_token_2022::{
+        extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+        state::Mint as MintState,
+    },
+    token_interface::Mint,
+};
+
+use crate::error::AmmDexError;
+
+// Amount that actually lands when `amount` is sent, after the mint's Token-2022
+// transfer fee. Classic mints and mints without the extension pass through.
+pub fn amount_after_transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+    let fee = match mint_state.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(AmmDexError::Overflow)?,
+        Err(_) => 0,
+    };
+    Ok(amount.checked_sub(fee).ok_or(AmmDexError::Underflow)?)
+}
+
+// Amount that has to be sent so that `amount` actually lands after the mint's
+// Token-2022 transfer fee. Classic mints and mints without the extension pass through.
+pub fn amount_before_transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+    let fee = match mint_state.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config
+            .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(AmmDexError::Overflow)?,
+        Err(_) => 0,
+    };
+    Ok(amount.checked_add(fee).ok_or(AmmDexError::Overflow)?)
+}
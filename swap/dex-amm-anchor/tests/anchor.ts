@@ -1,14 +1,2123 @@
 import * as anchor from "@coral-xyz/anchor";
-import { Program } from "@coral-xyz/anchor";
+import { Program, BN } from "@coral-xyz/anchor";
 import { Amm } from "../target/types/amm";
+import {
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  ExtensionType,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createMint,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getMintLen,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+  transfer,
+} from "@solana/spl-token";
+import { expect } from "chai";
+
+const PAUSE_DEPOSIT = 1 << 0;
+const PAUSE_WITHDRAW = 1 << 1;
+const PAUSE_SWAP = 1 << 2;
+const EMERGENCY_MODE = 1 << 3;
+const PAUSE_FLAGS_MASK =
+  PAUSE_DEPOSIT | PAUSE_WITHDRAW | PAUSE_SWAP | EMERGENCY_MODE;
+const MINIMUM_LIQUIDITY = 1_000;
 
 describe("amm", () => {
   // Configure the client to use the local cluster.
   anchor.setProvider(anchor.AnchorProvider.env());
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+  const connection = provider.connection;
 
   const program = anchor.workspace.amm as Program<Amm>;
+  const payer = (provider.wallet as anchor.Wallet).payer;
+  const seeds = new BN(1);
+
+  let mintX: anchor.web3.PublicKey;
+  let mintY: anchor.web3.PublicKey;
+  let poolConfig: anchor.web3.PublicKey;
+  let lpToken: anchor.web3.PublicKey;
+  let vaultX: anchor.web3.PublicKey;
+  let vaultY: anchor.web3.PublicKey;
+  let userX: anchor.web3.PublicKey;
+  let userY: anchor.web3.PublicKey;
+  let userLp: anchor.web3.PublicKey;
+
+  const poolAccounts = () => ({
+    user: payer.publicKey,
+    tokenXMint: mintX,
+    tokenYMint: mintY,
+    userXToken: userX,
+    userYToken: userY,
+    tokenXVault: vaultX,
+    tokenYVault: vaultY,
+    poolConfig,
+    lpToken,
+    userLpTokenAc: userLp,
+    associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    tokenXProgram: TOKEN_PROGRAM_ID,
+    tokenYProgram: TOKEN_PROGRAM_ID,
+    tokenProgram: TOKEN_PROGRAM_ID,
+  });
+
+  // Deadlines are unix timestamps; null trades without one
+  const deadlineArg = (deadline: number | null) => (deadline === null ? null : new BN(deadline));
+
+  const deposit = (amount: number, maxX: number, maxY: number, deadline: number | null = null) =>
+    program.methods
+      .deposit(new BN(amount), new BN(maxX), new BN(maxY), deadlineArg(deadline))
+      .accountsPartial(poolAccounts())
+      .rpc();
+
+  const withdraw = (amount: number, minX: number, minY: number, deadline: number | null = null) =>
+    program.methods
+      .withdraw(new BN(amount), new BN(minX), new BN(minY), deadlineArg(deadline))
+      .accountsPartial(poolAccounts())
+      .rpc();
+
+  const swap = (lpPairX: boolean, amount: number, min: number, deadline: number | null = null) =>
+    program.methods
+      .swap(lpPairX, new BN(amount), new BN(min), deadlineArg(deadline))
+      .accountsPartial(poolAccounts())
+      .rpc();
+
+  const swapExactOut = (lpPairX: boolean, amountOut: number, maxIn: number) =>
+    program.methods
+      .swapExactOut(lpPairX, new BN(amountOut), new BN(maxIn), null)
+      .accountsPartial(poolAccounts())
+      .rpc();
+
+  const balance = async (account: anchor.web3.PublicKey) =>
+    Number((await getAccount(connection, account)).amount);
+
+  const setPauseFlags = (flags: number) =>
+    program.methods
+      .updateConfig({ pauseFlags: { 0: flags } })
+      .accountsPartial({ user: payer.publicKey, poolConfig })
+      .rpc();
+
+  const expectError = async (promise: Promise<unknown>, code: string) => {
+    try {
+      await promise;
+    } catch (err) {
+      expect((err as anchor.AnchorError).error.errorCode.code).to.equal(code);
+      return;
+    }
+    expect.fail(`expected ${code}`);
+  };
+
+  // Events go out through a self-CPI, so they are decoded from inner instructions, not logs
+  const cpiEvents = async (signature: string) => {
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const keys = tx.transaction.message.staticAccountKeys;
+    return tx.meta.innerInstructions
+      .flatMap((inner) => inner.instructions)
+      .filter((ix) => keys[ix.programIdIndex].equals(program.programId))
+      .map((ix) => {
+        // Skip the 8-byte event instruction tag ahead of the event itself
+        const data = Buffer.from(anchor.utils.bytes.bs58.decode(ix.data)).subarray(8);
+        return program.coder.events.decode(data.toString("base64"));
+      })
+      .filter((event) => event !== null);
+  };
+
+  const expectOutcome = async (
+    promise: Promise<unknown>,
+    allowed: boolean,
+    code: string
+  ) => {
+    if (allowed) {
+      await promise;
+    } else {
+      await expectError(promise, code);
+    }
+  };
+
+  // Creates a classic-token pool over two fresh mints, funds the payer and seeds it with
+  // `initial` of each token, or leaves it empty when `initial` is zero
+  const createFundedPool = async (curveType: object, amp: number, initial = 1_000_000_000) => {
+    const a = await createMint(connection, payer, payer.publicKey, null, 6);
+    const b = await createMint(connection, payer, payer.publicKey, null, 6);
+    const [x, y] = Buffer.compare(a.toBuffer(), b.toBuffer()) < 0 ? [a, b] : [b, a];
+    const [pool] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("pool-config"), x.toBuffer(), y.toBuffer(), seeds.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [lp] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("lp-token"), pool.toBuffer()],
+      program.programId
+    );
+    const [ataX, ataY] = await Promise.all(
+      [x, y].map(async (mint) => {
+        const ata = (
+          await getOrCreateAssociatedTokenAccount(connection, payer, mint, payer.publicKey)
+        ).address;
+        await mintTo(connection, payer, mint, ata, payer, 10_000_000_000);
+        return ata;
+      })
+    );
+    const accounts = {
+      ...poolAccounts(),
+      tokenXMint: x,
+      tokenYMint: y,
+      userXToken: ataX,
+      userYToken: ataY,
+      tokenXVault: getAssociatedTokenAddressSync(x, pool, true),
+      tokenYVault: getAssociatedTokenAddressSync(y, pool, true),
+      poolConfig: pool,
+      lpToken: lp,
+      userLpTokenAc: getAssociatedTokenAddressSync(lp, payer.publicKey),
+    };
+    await program.methods
+      .initialize(seeds, 30, payer.publicKey, curveType, new BN(amp))
+      .accountsPartial({
+        tokenXMetadata: null,
+        tokenYMetadata: null,
+        owner: payer.publicKey,
+        tokenXMint: x,
+        tokenYMint: y,
+        poolConfig: pool,
+        lpToken: lp,
+        tokenXVault: accounts.tokenXVault,
+        tokenYVault: accounts.tokenYVault,
+        tokenXProgram: TOKEN_PROGRAM_ID,
+        tokenYProgram: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+    if (initial > 0) {
+      await program.methods
+        .deposit(new BN(initial), new BN(initial), new BN(initial), null)
+        .accountsPartial(accounts)
+        .rpc();
+    }
+    return accounts;
+  };
+
+  before(async () => {
+    const a = await createMint(connection, payer, payer.publicKey, null, 6);
+    const b = await createMint(connection, payer, payer.publicKey, null, 6);
+    // Pools store their pair in canonical order
+    [mintX, mintY] = Buffer.compare(a.toBuffer(), b.toBuffer()) < 0 ? [a, b] : [b, a];
+
+    [poolConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("pool-config"),
+        mintX.toBuffer(),
+        mintY.toBuffer(),
+        seeds.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+    [lpToken] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("lp-token"), poolConfig.toBuffer()],
+      program.programId
+    );
+    vaultX = getAssociatedTokenAddressSync(mintX, poolConfig, true);
+    vaultY = getAssociatedTokenAddressSync(mintY, poolConfig, true);
+    userLp = getAssociatedTokenAddressSync(lpToken, payer.publicKey);
+
+    userX = (
+      await getOrCreateAssociatedTokenAccount(connection, payer, mintX, payer.publicKey)
+    ).address;
+    userY = (
+      await getOrCreateAssociatedTokenAccount(connection, payer, mintY, payer.publicKey)
+    ).address;
+    await mintTo(connection, payer, mintX, userX, payer, 10_000_000_000);
+    await mintTo(connection, payer, mintY, userY, payer, 10_000_000_000);
+
+    // Every pool registers itself in the program-wide factory
+    await program.methods.initializeFactory().rpc();
+  });
 
   it("Is initialized!", async () => {
-    // Add your test here.
+    await program.methods
+      .initialize(seeds, 30, payer.publicKey, { constantProduct: {} }, new BN(0))
+      .accountsPartial({
+        tokenXMetadata: null,
+        tokenYMetadata: null,
+        owner: payer.publicKey,
+        tokenXMint: mintX,
+        tokenYMint: mintY,
+        poolConfig,
+        lpToken,
+        tokenXVault: vaultX,
+        tokenYVault: vaultY,
+        tokenXProgram: TOKEN_PROGRAM_ID,
+        tokenYProgram: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const config = await program.account.poolConfig.fetch(poolConfig);
+    expect(config.tokenXMint.toBase58()).to.equal(mintX.toBase58());
+    expect(config.tokenYMint.toBase58()).to.equal(mintY.toBase58());
+    expect(config.pauseFlags).to.equal(0);
+
+    await deposit(1_000_000_000, 1_000_000_000, 1_000_000_000);
+  });
+
+  describe("pause flags", () => {
+    after(async () => {
+      await setPauseFlags(0);
+    });
+
+    for (let flags = 0; flags <= PAUSE_FLAGS_MASK; flags++) {
+      it(`flags = 0b${flags.toString(2).padStart(4, "0")}`, async () => {
+        await setPauseFlags(flags);
+        const config = await program.account.poolConfig.fetch(poolConfig);
+        expect(config.pauseFlags).to.equal(flags);
+
+        const emergency = (flags & EMERGENCY_MODE) !== 0;
+        await expectOutcome(
+          deposit(1_000_000, 2_000_000, 2_000_000),
+          !emergency && (flags & PAUSE_DEPOSIT) === 0,
+          "DepositsPaused"
+        );
+        await expectOutcome(
+          swap(true, 1_000, 1),
+          !emergency && (flags & PAUSE_SWAP) === 0,
+          "SwapsPaused"
+        );
+        // Emergency mode always lets LPs exit pro-rata
+        await expectOutcome(
+          withdraw(1_000_000, 0, 0),
+          emergency || (flags & PAUSE_WITHDRAW) === 0,
+          "WithdrawalsPaused"
+        );
+      });
+    }
+
+    it("rejects unknown flags", async () => {
+      await expectError(setPauseFlags(1 << 4), "InvalidPauseFlags");
+    });
+
+    it("rejects updates from anyone but the owner", async () => {
+      const stranger = anchor.web3.Keypair.generate();
+      await expectError(
+        program.methods
+          .updateConfig({ pauseFlags: { 0: EMERGENCY_MODE } })
+          .accountsPartial({ user: stranger.publicKey, poolConfig })
+          .signers([stranger])
+          .rpc(),
+        "InvalidAuthority"
+      );
+    });
+  });
+
+  describe("swap errors", () => {
+    it("returns SlippageToleranceExceeded for a bad quote", async () => {
+      await expectError(swap(true, 1_000, 1_000_000), "SlippageToleranceExceeded");
+    });
+
+    it("returns ZeroOutput when the input is too small to move the curve", async () => {
+      await expectError(swap(true, 1, 1), "ZeroOutput");
+    });
+  });
+
+  describe("deadlines", () => {
+    const now = () => Math.floor(Date.now() / 1000);
+
+    it("rejects trades that land after their deadline", async () => {
+      const expired = now() - 60;
+      await expectError(swap(true, 1_000_000, 1, expired), "DeadlineExceeded");
+      await expectError(deposit(1_000_000, 10_000_000, 10_000_000, expired), "DeadlineExceeded");
+      await expectError(withdraw(1_000_000, 0, 0, expired), "DeadlineExceeded");
+    });
+
+    it("accepts trades before their deadline", async () => {
+      const later = now() + 600;
+      await swap(true, 1_000_000, 1, later);
+      await deposit(1_000_000, 10_000_000, 10_000_000, later);
+      await withdraw(1_000_000, 0, 0, later);
+    });
+  });
+
+  describe("swap exact out", () => {
+    it("pays out exactly the requested amount", async () => {
+      const xBefore = await balance(userX);
+      const yBefore = await balance(userY);
+      await swapExactOut(true, 1_000_000, 2_000_000);
+      expect((await balance(userY)) - yBefore).to.equal(1_000_000);
+      // Fees and rounding only ever cost the trader
+      expect(xBefore - (await balance(userX))).to.be.greaterThan(1_000_000);
+    });
+
+    it("enforces the maximum input", async () => {
+      await expectError(
+        swapExactOut(false, 1_000_000, 1_000_000),
+        "SlippageToleranceExceeded"
+      );
+    });
+  });
+
+  describe("route swap", () => {
+    // Second pool pairs Y with a fresh mint Z, so X -> Y -> Z crosses both pools
+    let mintZ: anchor.web3.PublicKey;
+    let userZ: anchor.web3.PublicKey;
+    let pool2: anchor.web3.PublicKey;
+    let pool2Mints: [anchor.web3.PublicKey, anchor.web3.PublicKey];
+    let pool2Lp: anchor.web3.PublicKey;
+
+    const leg = (
+      pool: anchor.web3.PublicKey,
+      [x, y]: [anchor.web3.PublicKey, anchor.web3.PublicKey],
+      lp: anchor.web3.PublicKey
+    ) =>
+      [
+        pool,
+        x,
+        y,
+        getAssociatedTokenAddressSync(x, pool, true),
+        getAssociatedTokenAddressSync(y, pool, true),
+        lp,
+        getAssociatedTokenAddressSync(x, payer.publicKey),
+        getAssociatedTokenAddressSync(y, payer.publicKey),
+      ].map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }));
+
+    const routeSwap = (amount: number, min: number) =>
+      program.methods
+        .routeSwap(
+          [true, pool2Mints[0].equals(mintY)],
+          new BN(amount),
+          new BN(min),
+          null
+        )
+        .accountsPartial({
+          user: payer.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          token2022Program: TOKEN_2022_PROGRAM_ID,
+        })
+        .remainingAccounts([
+          ...leg(poolConfig, [mintX, mintY], lpToken),
+          ...leg(pool2, pool2Mints, pool2Lp),
+        ])
+        .rpc();
+
+    before(async () => {
+      mintZ = await createMint(connection, payer, payer.publicKey, null, 6);
+      userZ = (
+        await getOrCreateAssociatedTokenAccount(connection, payer, mintZ, payer.publicKey)
+      ).address;
+      await mintTo(connection, payer, mintZ, userZ, payer, 10_000_000_000);
+
+      pool2Mints =
+        Buffer.compare(mintY.toBuffer(), mintZ.toBuffer()) < 0 ? [mintY, mintZ] : [mintZ, mintY];
+      [pool2] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("pool-config"),
+          pool2Mints[0].toBuffer(),
+          pool2Mints[1].toBuffer(),
+          seeds.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+      [pool2Lp] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("lp-token"), pool2.toBuffer()],
+        program.programId
+      );
+      const [vault2X, vault2Y] = pool2Mints.map((mint) =>
+        getAssociatedTokenAddressSync(mint, pool2, true)
+      );
+      const [user2X, user2Y] = pool2Mints.map((mint) =>
+        getAssociatedTokenAddressSync(mint, payer.publicKey)
+      );
+
+      await program.methods
+        .initialize(seeds, 30, payer.publicKey, { constantProduct: {} }, new BN(0))
+        .accountsPartial({
+          tokenXMetadata: null,
+          tokenYMetadata: null,
+          owner: payer.publicKey,
+          tokenXMint: pool2Mints[0],
+          tokenYMint: pool2Mints[1],
+          poolConfig: pool2,
+          lpToken: pool2Lp,
+          tokenXVault: vault2X,
+          tokenYVault: vault2Y,
+          tokenXProgram: TOKEN_PROGRAM_ID,
+          tokenYProgram: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      await program.methods
+        .deposit(
+          new BN(1_000_000_000),
+          new BN(1_000_000_000),
+          new BN(1_000_000_000),
+          null
+        )
+        .accountsPartial({
+          ...poolAccounts(),
+          tokenXMint: pool2Mints[0],
+          tokenYMint: pool2Mints[1],
+          userXToken: user2X,
+          userYToken: user2Y,
+          tokenXVault: vault2X,
+          tokenYVault: vault2Y,
+          poolConfig: pool2,
+          lpToken: pool2Lp,
+          userLpTokenAc: getAssociatedTokenAddressSync(pool2Lp, payer.publicKey),
+        })
+        .rpc();
+    });
+
+    it("swaps X -> Y -> Z in one instruction", async () => {
+      const xBefore = await balance(userX);
+      const yBefore = await balance(userY);
+      const zBefore = await balance(userZ);
+      await routeSwap(1_000_000, 1);
+      expect(xBefore - (await balance(userX))).to.equal(1_000_000);
+      // The intermediate token only passes through
+      expect(await balance(userY)).to.equal(yBefore);
+      expect((await balance(userZ)) - zBefore).to.be.greaterThan(0);
+    });
+
+    it("reverts every leg when the final output misses the minimum", async () => {
+      const xBefore = await balance(userX);
+      await expectError(routeSwap(1_000_000, 1_000_000), "SlippageToleranceExceeded");
+      expect(await balance(userX)).to.equal(xBefore);
+    });
+  });
+
+  describe("token-2022 transfer fees", () => {
+    // X is a Token-2022 mint charging 1% on every transfer, Y is a classic mint
+    const FEE_BPS = 100;
+    let feeMint: anchor.web3.PublicKey;
+    let classicMint: anchor.web3.PublicKey;
+    let accounts: ReturnType<typeof poolAccounts>;
+    let feeIsX: boolean;
+
+    const createTransferFeeMint = async () => {
+      const mint = anchor.web3.Keypair.generate();
+      const space = getMintLen([ExtensionType.TransferFeeConfig]);
+      const lamports = await connection.getMinimumBalanceForRentExemption(space);
+      const tx = new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          newAccountPubkey: mint.publicKey,
+          space,
+          lamports,
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        createInitializeTransferFeeConfigInstruction(
+          mint.publicKey,
+          payer.publicKey,
+          payer.publicKey,
+          FEE_BPS,
+          BigInt(1_000_000_000),
+          TOKEN_2022_PROGRAM_ID
+        ),
+        createInitializeMintInstruction(
+          mint.publicKey,
+          6,
+          payer.publicKey,
+          null,
+          TOKEN_2022_PROGRAM_ID
+        )
+      );
+      await provider.sendAndConfirm(tx, [mint]);
+      return mint.publicKey;
+    };
+
+    before(async () => {
+      feeMint = await createTransferFeeMint();
+      classicMint = await createMint(connection, payer, payer.publicKey, null, 6);
+      feeIsX = Buffer.compare(feeMint.toBuffer(), classicMint.toBuffer()) < 0;
+      const [x, y] = feeIsX ? [feeMint, classicMint] : [classicMint, feeMint];
+      const [xProgram, yProgram] = feeIsX
+        ? [TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID]
+        : [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID];
+
+      const [pool] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("pool-config"), x.toBuffer(), y.toBuffer(), seeds.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [lp] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("lp-token"), pool.toBuffer()],
+        program.programId
+      );
+      const userAccounts = await Promise.all(
+        [
+          [x, xProgram],
+          [y, yProgram],
+        ].map(async ([mint, tokenProgram]) => {
+          const ata = await getOrCreateAssociatedTokenAccount(
+            connection,
+            payer,
+            mint,
+            payer.publicKey,
+            false,
+            undefined,
+            undefined,
+            tokenProgram
+          );
+          await mintTo(
+            connection,
+            payer,
+            mint,
+            ata.address,
+            payer,
+            10_000_000_000,
+            [],
+            undefined,
+            tokenProgram
+          );
+          return ata.address;
+        })
+      );
+
+      accounts = {
+        ...poolAccounts(),
+        tokenXMint: x,
+        tokenYMint: y,
+        userXToken: userAccounts[0],
+        userYToken: userAccounts[1],
+        tokenXVault: getAssociatedTokenAddressSync(x, pool, true, xProgram),
+        tokenYVault: getAssociatedTokenAddressSync(y, pool, true, yProgram),
+        poolConfig: pool,
+        lpToken: lp,
+        userLpTokenAc: getAssociatedTokenAddressSync(lp, payer.publicKey),
+        tokenXProgram: xProgram,
+        tokenYProgram: yProgram,
+      };
+
+      await program.methods
+        .initialize(seeds, 30, payer.publicKey, { constantProduct: {} }, new BN(0))
+        .accountsPartial({
+          tokenXMetadata: null,
+          tokenYMetadata: null,
+          owner: payer.publicKey,
+          tokenXMint: x,
+          tokenYMint: y,
+          poolConfig: pool,
+          lpToken: lp,
+          tokenXVault: accounts.tokenXVault,
+          tokenYVault: accounts.tokenYVault,
+          tokenXProgram: xProgram,
+          tokenYProgram: yProgram,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      await program.methods
+        .deposit(
+          new BN(1_000_000_000),
+          new BN(1_000_000_000),
+          new BN(1_000_000_000),
+          null
+        )
+        .accountsPartial(accounts)
+        .rpc();
+    });
+
+    const vaultBalance = async (vault: anchor.web3.PublicKey) =>
+      Number((await getAccount(connection, vault, undefined, TOKEN_2022_PROGRAM_ID)).amount);
+
+    it("credits only what reached the vault on deposit", async () => {
+      const feeVault = feeIsX ? accounts.tokenXVault : accounts.tokenYVault;
+      // 1% of the 1_000_000_000 first deposit was withheld by the mint
+      expect(await vaultBalance(feeVault)).to.equal(990_000_000);
+    });
+
+    it("prices swaps on the amount received after the transfer fee", async () => {
+      const feeVault = feeIsX ? accounts.tokenXVault : accounts.tokenYVault;
+      const before = await vaultBalance(feeVault);
+      await program.methods
+        .swap(feeIsX, new BN(10_000_000), new BN(1), null)
+        .accountsPartial(accounts)
+        .rpc();
+      expect((await vaultBalance(feeVault)) - before).to.equal(9_900_000);
+    });
+  });
+
+  describe("stable swap curve", () => {
+    let stable: Awaited<ReturnType<typeof createFundedPool>>;
+    let constantProduct: Awaited<ReturnType<typeof createFundedPool>>;
+
+    const swapOut = async (accounts: typeof stable) => {
+      const before = await balance(accounts.userYToken);
+      await program.methods
+        .swap(true, new BN(10_000_000), new BN(1), null)
+        .accountsPartial(accounts)
+        .rpc();
+      return (await balance(accounts.userYToken)) - before;
+    };
+
+    before(async () => {
+      stable = await createFundedPool({ stableSwap: {} }, 100);
+      constantProduct = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("stores the curve and amplification", async () => {
+      const config = await program.account.poolConfig.fetch(stable.poolConfig);
+      expect(config.curveType).to.deep.equal({ stableSwap: {} });
+      expect(config.ampTarget.toNumber()).to.equal(100);
+    });
+
+    it("gives a balanced pool far less slippage than constant product", async () => {
+      const stableOut = await swapOut(stable);
+      const constantProductOut = await swapOut(constantProduct);
+      expect(stableOut).to.be.greaterThan(constantProductOut);
+      // Only fees separate a balanced StableSwap trade from 1:1
+      expect(stableOut).to.be.greaterThan(9_950_000);
+    });
+
+    it("rejects amplification ramps that are too fast", async () => {
+      const now = Math.floor(Date.now() / 1000);
+      await expectError(
+        program.methods
+          .updateConfig({ ampRamp: { 0: { targetAmp: new BN(200), rampEnd: new BN(now + 60) } } })
+          .accountsPartial({ user: payer.publicKey, poolConfig: stable.poolConfig })
+          .rpc(),
+        "InvalidAmpRamp"
+      );
+    });
+
+    it("starts a ramp towards the target amplification", async () => {
+      const now = Math.floor(Date.now() / 1000);
+      await program.methods
+        .updateConfig({
+          ampRamp: { 0: { targetAmp: new BN(200), rampEnd: new BN(now + 2 * 86_400) } },
+        })
+        .accountsPartial({ user: payer.publicKey, poolConfig: stable.poolConfig })
+        .rpc();
+      const config = await program.account.poolConfig.fetch(stable.poolConfig);
+      expect(config.ampInitial.toNumber()).to.equal(100);
+      expect(config.ampTarget.toNumber()).to.equal(200);
+    });
+
+    it("rejects ramps on constant product pools", async () => {
+      const now = Math.floor(Date.now() / 1000);
+      await expectError(
+        program.methods
+          .updateConfig({
+            ampRamp: { 0: { targetAmp: new BN(200), rampEnd: new BN(now + 2 * 86_400) } },
+          })
+          .accountsPartial({ user: payer.publicKey, poolConfig: constantProduct.poolConfig })
+          .rpc(),
+        "InvalidAmpRamp"
+      );
+    });
+  });
+
+  describe("twap oracle", () => {
+    const Q64 = new BN(1).shln(64);
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+
+    const observe = (window: number) =>
+      program.methods.observe(window).accountsPartial({ poolConfig: pool.poolConfig });
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("records the spot price left by the first deposit", async () => {
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.lastPriceX.eq(Q64)).to.equal(true);
+      expect(config.lastPriceY.eq(Q64)).to.equal(true);
+    });
+
+    it("accumulates the previous price when a swap moves the reserves", async () => {
+      const before = await program.account.poolConfig.fetch(pool.poolConfig);
+      await new Promise((resolve) => setTimeout(resolve, 2_000));
+      await program.methods
+        .swap(true, new BN(10_000_000), new BN(1), null)
+        .accountsPartial(pool)
+        .rpc();
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      const elapsed = new BN(config.oracleUpdatedAt.toNumber() - before.oracleUpdatedAt.toNumber());
+      expect(elapsed.toNumber()).to.be.at.least(1);
+      expect(config.priceXCumulative.toString()).to.equal(
+        before.priceXCumulative.add(before.lastPriceX.mul(elapsed)).toString()
+      );
+      expect(config.priceYCumulative.toString()).to.equal(
+        before.priceYCumulative.add(before.lastPriceY.mul(elapsed)).toString()
+      );
+      // Selling X makes X cheaper and Y dearer
+      expect(config.lastPriceX.lt(Q64)).to.equal(true);
+      expect(config.lastPriceY.gt(Q64)).to.equal(true);
+    });
+
+    it("returns the time-weighted price over the requested window", async () => {
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      // Observations are kept every two minutes, so the window starts at the one
+      // initialize recorded
+      expect(config.observationIndex).to.equal(0);
+      const [start] = config.observations;
+      const twap = await observe(1).view();
+      const now = start.timestamp.toNumber() + twap.window.toNumber();
+      // Extend the accumulators from the last update to the time observe ran at
+      const sinceUpdate = new BN(now - config.oracleUpdatedAt.toNumber());
+      const expected = (cumulative: BN, lastPrice: BN, startCumulative: BN) =>
+        cumulative.add(lastPrice.mul(sinceUpdate)).sub(startCumulative).div(twap.window);
+      expect(twap.priceX.toString()).to.equal(
+        expected(config.priceXCumulative, config.lastPriceX, start.priceXCumulative).toString()
+      );
+      expect(twap.priceY.toString()).to.equal(
+        expected(config.priceYCumulative, config.lastPriceY, start.priceYCumulative).toString()
+      );
+      expect(twap.priceX.gt(new BN(0))).to.equal(true);
+      expect(twap.priceX.lt(Q64)).to.equal(true);
+    });
+
+    it("rejects windows longer than the stored history", async () => {
+      await expectError(observe(86_400).rpc(), "OracleWindowUnavailable");
+    });
+
+    it("rejects an empty window", async () => {
+      await expectError(observe(0).rpc(), "InvalidAmount");
+    });
+  });
+
+  describe("protocol fees", () => {
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+
+    const claimAccounts = (recipient: anchor.web3.PublicKey) => ({
+      feeRecipient: recipient,
+      tokenXMint: pool.tokenXMint,
+      tokenYMint: pool.tokenYMint,
+      feeRecipientXToken: getAssociatedTokenAddressSync(pool.tokenXMint, recipient),
+      feeRecipientYToken: getAssociatedTokenAddressSync(pool.tokenYMint, recipient),
+      tokenXVault: pool.tokenXVault,
+      tokenYVault: pool.tokenYVault,
+      poolConfig: pool.poolConfig,
+      tokenXProgram: TOKEN_PROGRAM_ID,
+      tokenYProgram: TOKEN_PROGRAM_ID,
+    });
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("accrues the protocol share of each swap input", async () => {
+      await program.methods
+        .swap(true, new BN(10_000_000), new BN(1), null)
+        .accountsPartial(pool)
+        .rpc();
+      await program.methods
+        .swap(false, new BN(20_000_000), new BN(1), null)
+        .accountsPartial(pool)
+        .rpc();
+      // amount * 5 / 10_000 at the default protocol fee
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.protocolFeesX.toNumber()).to.equal(5_000);
+      expect(config.protocolFeesY.toNumber()).to.equal(10_000);
+    });
+
+    it("rejects a claim from anyone but the fee recipient", async () => {
+      const stranger = anchor.web3.Keypair.generate();
+      await connection.confirmTransaction(
+        await connection.requestAirdrop(stranger.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+      );
+      await expectError(
+        program.methods
+          .claimProtocolFees()
+          .accountsPartial(claimAccounts(stranger.publicKey))
+          .signers([stranger])
+          .rpc(),
+        "InvalidAuthority"
+      );
+    });
+
+    it("pays the accrued fees to the recipient and resets them", async () => {
+      const [xBefore, yBefore] = await Promise.all([
+        balance(pool.userXToken),
+        balance(pool.userYToken),
+      ]);
+      await program.methods
+        .claimProtocolFees()
+        .accountsPartial(claimAccounts(payer.publicKey))
+        .rpc();
+      expect((await balance(pool.userXToken)) - xBefore).to.equal(5_000);
+      expect((await balance(pool.userYToken)) - yBefore).to.equal(10_000);
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.protocolFeesX.toNumber()).to.equal(0);
+      expect(config.protocolFeesY.toNumber()).to.equal(0);
+    });
+
+    it("rejects a claim with nothing accrued", async () => {
+      await expectError(
+        program.methods
+          .claimProtocolFees()
+          .accountsPartial(claimAccounts(payer.publicKey))
+          .rpc(),
+        "InvalidAmount"
+      );
+    });
+  });
+
+  describe("flash loans", () => {
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+
+    const loanAccounts = () => ({
+      user: payer.publicKey,
+      tokenXMint: pool.tokenXMint,
+      tokenYMint: pool.tokenYMint,
+      userXToken: pool.userXToken,
+      userYToken: pool.userYToken,
+      tokenXVault: pool.tokenXVault,
+      tokenYVault: pool.tokenYVault,
+      poolConfig: pool.poolConfig,
+      tokenXProgram: TOKEN_PROGRAM_ID,
+      tokenYProgram: TOKEN_PROGRAM_ID,
+    });
+    const repayIx = () => program.methods.flashRepay().accountsPartial(loanAccounts()).instruction();
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("lends from the vault and keeps the fee for LPs", async () => {
+      const vaultBefore = await balance(pool.tokenXVault);
+      await program.methods
+        .flashLoan(true, new BN(100_000_000))
+        .accountsPartial(loanAccounts())
+        .postInstructions([await repayIx()])
+        .rpc();
+      // 0.09% of the loan, rounded up
+      expect((await balance(pool.tokenXVault)) - vaultBefore).to.equal(90_000);
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.activeLoan).to.equal(null);
+      expect(config.protocolFeesX.toNumber()).to.equal(0);
+    });
+
+    it("refuses a loan without a flash_repay later in the transaction", async () => {
+      await expectError(
+        program.methods
+          .flashLoan(true, new BN(100_000_000))
+          .accountsPartial(loanAccounts())
+          .rpc(),
+        "MissingFlashRepay"
+      );
+    });
+
+    it("refuses to lend more than the reserves", async () => {
+      await expectError(
+        program.methods
+          .flashLoan(false, new BN(10_000_000_000))
+          .accountsPartial(loanAccounts())
+          .postInstructions([await repayIx()])
+          .rpc(),
+        "InsufficientBalance"
+      );
+    });
+
+    it("blocks swaps and deposits while a loan is open", async () => {
+      const swapIx = await program.methods
+        .swap(true, new BN(1_000_000), new BN(1), null)
+        .accountsPartial(pool)
+        .instruction();
+      const depositIx = await program.methods
+        .deposit(new BN(1_000_000), new BN(10_000_000), new BN(10_000_000), null)
+        .accountsPartial(pool)
+        .instruction();
+      for (const ix of [swapIx, depositIx]) {
+        await expectError(
+          program.methods
+            .flashLoan(true, new BN(100_000_000))
+            .accountsPartial(loanAccounts())
+            .postInstructions([ix, await repayIx()])
+            .rpc(),
+          "FlashLoanActive"
+        );
+      }
+    });
+
+    it("blocks protocol fee claims while a loan is open", async () => {
+      await program.methods
+        .swap(true, new BN(10_000_000), new BN(1), null)
+        .accountsPartial(pool)
+        .rpc();
+      const claimIx = await program.methods
+        .claimProtocolFees()
+        .accountsPartial({ ...loanAccounts(), feeRecipient: payer.publicKey })
+        .instruction();
+      await expectError(
+        program.methods
+          .flashLoan(true, new BN(100_000_000))
+          .accountsPartial(loanAccounts())
+          .postInstructions([claimIx, await repayIx()])
+          .rpc(),
+        "FlashLoanActive"
+      );
+    });
+
+    it("rejects a repay with no open loan", async () => {
+      await expectError(
+        program.methods.flashRepay().accountsPartial(loanAccounts()).rpc(),
+        "FlashLoanNotActive"
+      );
+    });
+  });
+
+  describe("single-sided deposit", () => {
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+
+    const depositSingle = (lpPairX: boolean, amount: number, minLp: number) =>
+      program.methods
+        .depositSingle(lpPairX, new BN(amount), new BN(minLp), null)
+        .accountsPartial(pool)
+        .rpc();
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("mints LP for one token at roughly half its value in each side", async () => {
+      const lpBefore = await balance(pool.userLpTokenAc);
+      const yBefore = await balance(pool.userYToken);
+      await depositSingle(true, 10_000_000, 1);
+      const minted = (await balance(pool.userLpTokenAc)) - lpBefore;
+      // Half of 1% of the pool, less the fees and slippage of the internal trade
+      expect(minted).to.be.greaterThan(4_950_000);
+      expect(minted).to.be.lessThan(5_000_000);
+      expect(await balance(pool.userYToken)).to.equal(yBefore);
+    });
+
+    it("enforces the minimum LP out", async () => {
+      await expectError(depositSingle(false, 10_000_000, 5_000_000), "SlippageToleranceExceeded");
+    });
+
+    it("rejects a deposit too small to trade any of it", async () => {
+      await expectError(depositSingle(true, 1, 1), "InvalidAmount");
+    });
+  });
+
+  describe("single-sided withdraw", () => {
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+
+    const withdrawSingle = (lpPairX: boolean, amount: number, minOut: number) =>
+      program.methods
+        .withdrawSingle(lpPairX, new BN(amount), new BN(minOut), null)
+        .accountsPartial(pool)
+        .rpc();
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("pays out only the requested token", async () => {
+      const xBefore = await balance(pool.userXToken);
+      const yBefore = await balance(pool.userYToken);
+      await withdrawSingle(true, 10_000_000, 1);
+      const received = (await balance(pool.userXToken)) - xBefore;
+      // 1% of each side, with the Y share sold back for X after fees and slippage
+      expect(received).to.be.greaterThan(19_700_000);
+      expect(received).to.be.lessThan(20_000_000);
+      expect(await balance(pool.userYToken)).to.equal(yBefore);
+    });
+
+    it("enforces the minimum out", async () => {
+      await expectError(withdrawSingle(false, 10_000_000, 20_000_000), "SlippageToleranceExceeded");
+    });
+  });
+
+  describe("minimum liquidity", () => {
+    const lockedLp = (accounts: Awaited<ReturnType<typeof createFundedPool>>) => {
+      const [locked] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("locked-liquidity"), accounts.poolConfig.toBuffer()],
+        program.programId
+      );
+      return getAssociatedTokenAddressSync(accounts.lpToken, locked, true);
+    };
+
+    it("mints sqrt(x * y) and locks MINIMUM_LIQUIDITY for good", async () => {
+      const pool = await createFundedPool({ constantProduct: {} }, 0, 4_000_000);
+      // sqrt(4_000_000 * 4_000_000)
+      expect(await balance(lockedLp(pool))).to.equal(MINIMUM_LIQUIDITY);
+      expect(await balance(pool.userLpTokenAc)).to.equal(4_000_000 - MINIMUM_LIQUIDITY);
+    });
+
+    it("rejects a first deposit that cannot cover the locked liquidity", async () => {
+      await expectError(
+        createFundedPool({ constantProduct: {} }, 0, MINIMUM_LIQUIDITY),
+        "InsufficientInitialLiquidity"
+      );
+    });
+
+    it("makes inflating the share price by donation a losing trade", async () => {
+      // The attacker seeds the pool with a single LP token...
+      const pool = await createFundedPool({ constantProduct: {} }, 0, MINIMUM_LIQUIDITY + 1);
+      expect(await balance(pool.userLpTokenAc)).to.equal(1);
+      const donation = 1_000_000_000;
+      const [xBefore, yBefore] = await Promise.all([
+        balance(pool.userXToken),
+        balance(pool.userYToken),
+      ]);
+      // ...donates to the vaults and syncs the donation into the reserves, since tracked
+      // reserves ignore raw transfers, to make every share expensive
+      await transfer(connection, payer, pool.userXToken, pool.tokenXVault, payer, donation);
+      await transfer(connection, payer, pool.userYToken, pool.tokenYVault, payer, donation);
+      await program.methods.sync().accountsPartial(pool).rpc();
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.reserveX.toNumber()).to.equal(MINIMUM_LIQUIDITY + 1 + donation);
+
+      // A victim zaps in and loses at most one share to rounding
+      const victim = anchor.web3.Keypair.generate();
+      await connection.confirmTransaction(
+        await connection.requestAirdrop(victim.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+      );
+      const victimX = (
+        await getOrCreateAssociatedTokenAccount(connection, payer, pool.tokenXMint, victim.publicKey)
+      ).address;
+      const victimY = (
+        await getOrCreateAssociatedTokenAccount(connection, payer, pool.tokenYMint, victim.publicKey)
+      ).address;
+      const victimLp = getAssociatedTokenAddressSync(pool.lpToken, victim.publicKey);
+      await mintTo(connection, payer, pool.tokenXMint, victimX, payer, 100_000_000);
+      await program.methods
+        .depositSingle(true, new BN(100_000_000), new BN(1), null)
+        .accountsPartial({
+          ...pool,
+          user: victim.publicKey,
+          userXToken: victimX,
+          userYToken: victimY,
+          userLpTokenAc: victimLp,
+        })
+        .signers([victim])
+        .rpc();
+      expect(await balance(victimLp)).to.be.greaterThan(0);
+
+      // The locked shares soak up almost all of the donation, so cashing out
+      // returns the attacker a sliver of what they put in on each side
+      await program.methods
+        .withdraw(new BN(1), new BN(0), new BN(0), null)
+        .accountsPartial(pool)
+        .rpc();
+      const spentX = xBefore - (await balance(pool.userXToken));
+      const spentY = yBefore - (await balance(pool.userYToken));
+      expect(spentX).to.be.greaterThan(donation * 0.99);
+      expect(spentY).to.be.greaterThan(donation * 0.99);
+    });
+  });
+
+  describe("tracked reserves", () => {
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+
+    const reserves = async () => {
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      return [config.reserveX.toNumber(), config.reserveY.toNumber()];
+    };
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("ignores tokens sent straight to a vault", async () => {
+      const [x, y] = await reserves();
+      await transfer(connection, payer, pool.userXToken, pool.tokenXVault, payer, 50_000_000);
+      expect(await reserves()).to.deep.equal([x, y]);
+    });
+
+    it("skims the excess to a recipient without touching the reserves", async () => {
+      const before = await reserves();
+      const xBefore = await balance(pool.userXToken);
+      await program.methods
+        .skim()
+        .accountsPartial({
+          ...pool,
+          recipientXToken: pool.userXToken,
+          recipientYToken: pool.userYToken,
+        })
+        .rpc();
+      expect((await balance(pool.userXToken)) - xBefore).to.equal(50_000_000);
+      expect(await reserves()).to.deep.equal(before);
+    });
+
+    it("syncs the reserves up to the vault balances", async () => {
+      const [x, y] = await reserves();
+      await transfer(connection, payer, pool.userYToken, pool.tokenYVault, payer, 25_000_000);
+      await program.methods.sync().accountsPartial(pool).rpc();
+      expect(await reserves()).to.deep.equal([x, y + 25_000_000]);
+    });
+  });
+
+  describe("config updates", () => {
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+
+    const updateConfig = (param: object) =>
+      program.methods
+        .updateConfig(param)
+        .accountsPartial({ user: payer.publicKey, poolConfig: pool.poolConfig })
+        .rpc({ commitment: "confirmed" });
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("sets the LP and protocol fees", async () => {
+      await updateConfig({ lpFee: { 0: 100 } });
+      await updateConfig({ protocolFee: { 0: 50 } });
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.lpFee).to.equal(100);
+      expect(config.protocolFee).to.equal(50);
+    });
+
+    it("rejects fees above their caps", async () => {
+      await expectError(updateConfig({ lpFee: { 0: 1_001 } }), "FeeTooHigh");
+      await expectError(updateConfig({ protocolFee: { 0: 101 } }), "FeeTooHigh");
+    });
+
+    it("moves protocol fees to a new recipient", async () => {
+      const recipient = anchor.web3.Keypair.generate().publicKey;
+      await updateConfig({ feeRecipient: { 0: recipient } });
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.feeRecipient.toBase58()).to.equal(recipient.toBase58());
+    });
+
+    it("clears the fee recipient", async () => {
+      await updateConfig({ feeRecipient: { 0: null } });
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.feeRecipient).to.equal(null);
+    });
+
+    it("emits a PoolUpdated event for each change", async () => {
+      const events = await cpiEvents(await updateConfig({ lpFee: { 0: 30 } }));
+      expect(events.map((event) => event.name)).to.deep.equal(["poolUpdated"]);
+      expect(events[0].data.param).to.deep.equal({ lpFee: { 0: 30 } });
+    });
+  });
+
+  describe("quotes", () => {
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("quotes exactly what the swap then pays", async () => {
+      const quote = await program.methods
+        .quoteSwap(true, new BN(50_000_000))
+        .accountsPartial(pool)
+        .view();
+      const before = await balance(pool.userYToken);
+      await program.methods
+        .swap(true, new BN(50_000_000), new BN(1), null)
+        .accountsPartial(pool)
+        .rpc();
+      expect((await balance(pool.userYToken)) - before).to.equal(quote.amountOut.toNumber());
+      expect(quote.lpFee).to.equal(30);
+      expect(quote.protocolFee.toNumber()).to.equal(25_000);
+      // 5% of the reserves moves a constant product price by roughly 10%
+      expect(quote.priceImpactBps.toNumber()).to.be.within(900, 1_100);
+    });
+
+    it("quotes exactly what a deposit takes and mints", async () => {
+      const quote = await program.methods
+        .quoteDeposit(new BN(1_000_000), new BN(10_000_000), new BN(10_000_000))
+        .accountsPartial(pool)
+        .view();
+      const [xBefore, yBefore] = await Promise.all([
+        balance(pool.userXToken),
+        balance(pool.userYToken),
+      ]);
+      const lpBefore = await balance(pool.userLpTokenAc);
+      await program.methods
+        .deposit(new BN(1_000_000), new BN(10_000_000), new BN(10_000_000), null)
+        .accountsPartial(pool)
+        .rpc();
+      expect(xBefore - (await balance(pool.userXToken))).to.equal(quote.amountX.toNumber());
+      expect(yBefore - (await balance(pool.userYToken))).to.equal(quote.amountY.toNumber());
+      expect((await balance(pool.userLpTokenAc)) - lpBefore).to.equal(quote.lpAmount.toNumber());
+      expect(quote.protocolFee.toNumber()).to.equal(0);
+    });
+
+    it("quotes exactly what a withdraw pays out", async () => {
+      const quote = await program.methods
+        .quoteWithdraw(new BN(1_000_000))
+        .accountsPartial(pool)
+        .view();
+      const [xBefore, yBefore] = await Promise.all([
+        balance(pool.userXToken),
+        balance(pool.userYToken),
+      ]);
+      await program.methods
+        .withdraw(new BN(1_000_000), new BN(0), new BN(0), null)
+        .accountsPartial(pool)
+        .rpc();
+      expect((await balance(pool.userXToken)) - xBefore).to.equal(quote.amountX.toNumber());
+      expect((await balance(pool.userYToken)) - yBefore).to.equal(quote.amountY.toNumber());
+    });
+
+    it("quotes exactly what an exact-out swap charges", async () => {
+      const quote = await program.methods
+        .quoteSwapExactOut(false, new BN(20_000_000))
+        .accountsPartial(pool)
+        .view();
+      const before = await balance(pool.userYToken);
+      await program.methods
+        .swapExactOut(false, new BN(20_000_000), quote.amountIn, null)
+        .accountsPartial(pool)
+        .rpc();
+      expect(before - (await balance(pool.userYToken))).to.equal(quote.amountIn.toNumber());
+      expect(quote.amountOut.toNumber()).to.equal(20_000_000);
+    });
+
+    it("quotes exactly what a single-sided deposit mints", async () => {
+      const quote = await program.methods
+        .quoteDepositSingle(true, new BN(10_000_000))
+        .accountsPartial(pool)
+        .view();
+      const lpBefore = await balance(pool.userLpTokenAc);
+      const [event] = await cpiEvents(
+        await program.methods
+          .depositSingle(true, new BN(10_000_000), quote.lpAmount, null)
+          .accountsPartial(pool)
+          .rpc({ commitment: "confirmed" })
+      );
+      expect((await balance(pool.userLpTokenAc)) - lpBefore).to.equal(quote.lpAmount.toNumber());
+      expect(quote.amountX.toNumber()).to.equal(10_000_000);
+      expect(quote.amountY.toNumber()).to.equal(0);
+      expect(quote.lpFee).to.equal(event.data.lpFee);
+      expect(quote.protocolFee.toNumber()).to.equal(event.data.protocolFee.toNumber());
+      expect(quote.priceImpactBps.toNumber()).to.be.greaterThan(0);
+    });
+
+    it("quotes exactly what a single-sided withdraw pays out", async () => {
+      const quote = await program.methods
+        .quoteWithdrawSingle(false, new BN(1_000_000))
+        .accountsPartial(pool)
+        .view();
+      const before = await balance(pool.userYToken);
+      const [event] = await cpiEvents(
+        await program.methods
+          .withdrawSingle(false, new BN(1_000_000), quote.amountY, null)
+          .accountsPartial(pool)
+          .rpc({ commitment: "confirmed" })
+      );
+      expect((await balance(pool.userYToken)) - before).to.equal(quote.amountY.toNumber());
+      expect(quote.amountX.toNumber()).to.equal(0);
+      expect(quote.lpFee).to.equal(event.data.lpFee);
+      expect(quote.protocolFee.toNumber()).to.equal(event.data.protocolFee.toNumber());
+      expect(quote.priceImpactBps.toNumber()).to.be.greaterThan(0);
+    });
+  });
+
+  describe("factory", () => {
+    const registration = (x: anchor.web3.PublicKey, y: anchor.web3.PublicKey, fee: number) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("pool-registration"),
+          x.toBuffer(),
+          y.toBuffer(),
+          new BN(fee).toArrayLike(Buffer, "le", 2),
+        ],
+        program.programId
+      )[0];
+
+    const initialize = (
+      pool: Awaited<ReturnType<typeof createFundedPool>>,
+      poolSeeds: BN,
+      fee: number,
+      owner: anchor.web3.PublicKey | null = payer.publicKey
+    ) => {
+      const [poolConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("pool-config"),
+          pool.tokenXMint.toBuffer(),
+          pool.tokenYMint.toBuffer(),
+          poolSeeds.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+      return program.methods
+        .initialize(poolSeeds, fee, owner, { constantProduct: {} }, new BN(0))
+        .accountsPartial({
+          tokenXMetadata: null,
+          tokenYMetadata: null,
+          owner: payer.publicKey,
+          tokenXMint: pool.tokenXMint,
+          tokenYMint: pool.tokenYMint,
+          poolConfig,
+          tokenXProgram: TOKEN_PROGRAM_ID,
+          tokenYProgram: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+    };
+
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("registers each pool under its pair and fee tier", async () => {
+      const entry = await program.account.poolRegistration.fetch(
+        registration(pool.tokenXMint, pool.tokenYMint, 30)
+      );
+      expect(entry.poolConfig.toBase58()).to.equal(pool.poolConfig.toBase58());
+      expect(entry.feeTier).to.equal(30);
+    });
+
+    it("counts the pools it has registered", async () => {
+      const [factory] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("factory")],
+        program.programId
+      );
+      const before = (await program.account.factory.fetch(factory)).poolCount.toNumber();
+      await initialize(pool, new BN(1), 100);
+      const after = await program.account.factory.fetch(factory);
+      expect(after.poolCount.toNumber()).to.equal(before + 1);
+      const entry = await program.account.poolRegistration.fetch(
+        registration(pool.tokenXMint, pool.tokenYMint, 100)
+      );
+      expect(entry.index.toNumber()).to.equal(before);
+    });
+
+    it("rejects a second pool for the same pair and fee tier", async () => {
+      // `init` on the existing registration fails inside the system program
+      const taken = registration(pool.tokenXMint, pool.tokenYMint, 30).toBase58();
+      try {
+        await initialize(pool, new BN(2), 30);
+      } catch (err) {
+        const logs = (err as anchor.web3.SendTransactionError).logs.join("\n");
+        expect(logs).to.include(`address: ${taken}`);
+        expect(logs).to.include("already in use");
+        return;
+      }
+      expect.fail("expected the registration to be in use");
+    });
+
+    it("rejects a pool without an owner", async () => {
+      await expectError(initialize(pool, new BN(3), 5, null), "InvalidOwner");
+      const entry = await program.account.poolRegistration.fetchNullable(
+        registration(pool.tokenXMint, pool.tokenYMint, 5)
+      );
+      expect(entry).to.be.null;
+    });
+  });
+
+  describe("close pool", () => {
+    const closePool = (pool: Awaited<ReturnType<typeof createFundedPool>>, signer = payer) =>
+      program.methods
+        .closePool()
+        .accountsPartial({
+          ...pool,
+          owner: signer.publicKey,
+          poolRegistration: anchor.web3.PublicKey.findProgramAddressSync(
+            [
+              Buffer.from("pool-registration"),
+              pool.tokenXMint.toBuffer(),
+              pool.tokenYMint.toBuffer(),
+              new BN(30).toArrayLike(Buffer, "le", 2),
+            ],
+            program.programId
+          )[0],
+        })
+        .signers([signer])
+        .rpc();
+
+    it("refuses to close a pool holding liquidity", async () => {
+      const pool = await createFundedPool({ constantProduct: {} }, 0);
+      await expectError(closePool(pool), "PoolNotEmpty");
+    });
+
+    it("refuses to close for anyone but the owner", async () => {
+      const pool = await createFundedPool({ constantProduct: {} }, 0, 0);
+      await expectError(closePool(pool, anchor.web3.Keypair.generate()), "InvalidAuthority");
+    });
+
+    it("closes an empty pool and returns the rent to the owner", async () => {
+      const pool = await createFundedPool({ constantProduct: {} }, 0, 0);
+      const rent = await Promise.all(
+        [pool.poolConfig, pool.tokenXVault, pool.tokenYVault].map((key) =>
+          connection.getBalance(key)
+        )
+      );
+      const before = await connection.getBalance(payer.publicKey);
+      await closePool(pool);
+      const after = await connection.getBalance(payer.publicKey);
+      // Less the transaction fee
+      expect(after - before).to.be.greaterThan(rent.reduce((a, b) => a + b) - 10_000);
+      for (const key of [pool.poolConfig, pool.tokenXVault, pool.tokenYVault]) {
+        expect(await connection.getAccountInfo(key)).to.be.null;
+      }
+    });
+
+    it("leaves the LP mint behind, so the pool cannot be created again", async () => {
+      const pool = await createFundedPool({ constantProduct: {} }, 0, 0);
+      await closePool(pool);
+      try {
+        await program.methods
+          .initialize(seeds, 30, payer.publicKey, { constantProduct: {} }, new BN(0))
+          .accountsPartial({
+            tokenXMetadata: null,
+            tokenYMetadata: null,
+            ...pool,
+            owner: payer.publicKey,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+      } catch (err) {
+        const logs = (err as anchor.web3.SendTransactionError).logs.join("\n");
+        expect(logs).to.include(`address: ${pool.lpToken.toBase58()}`);
+        expect(logs).to.include("already in use");
+        return;
+      }
+      expect.fail("expected the LP mint to be in use");
+    });
+  });
+
+  describe("events", () => {
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+
+    const send = (method: { accountsPartial: (accounts: object) => any }) =>
+      method.accountsPartial(pool).rpc({ commitment: "confirmed" });
+
+    const expectReserves = async (data: { reserveX: BN; reserveY: BN }) => {
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(data.reserveX.toNumber()).to.equal(config.reserveX.toNumber());
+      expect(data.reserveY.toNumber()).to.equal(config.reserveY.toNumber());
+    };
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("emits LiquidityAdded with the post-deposit reserves", async () => {
+      const [event] = await cpiEvents(
+        await send(
+          program.methods.deposit(new BN(1_000_000), new BN(2_000_000), new BN(2_000_000), null)
+        )
+      );
+      expect(event.name).to.equal("liquidityAdded");
+      expect(event.data.poolConfig.toBase58()).to.equal(pool.poolConfig.toBase58());
+      expect(event.data.user.toBase58()).to.equal(payer.publicKey.toBase58());
+      expect(event.data.lpAmount.toNumber()).to.equal(1_000_000);
+      await expectReserves(event.data);
+    });
+
+    it("emits Swapped with the fees charged", async () => {
+      const [event] = await cpiEvents(
+        await send(program.methods.swap(true, new BN(10_000_000), new BN(1), null))
+      );
+      expect(event.name).to.equal("swapped");
+      expect(event.data.amountIn.toNumber()).to.equal(10_000_000);
+      expect(event.data.lpFee).to.equal(30);
+      expect(event.data.protocolFee.toNumber()).to.equal(5_000);
+      await expectReserves(event.data);
+    });
+
+    it("emits LiquidityRemoved for a single-sided withdraw", async () => {
+      const [event] = await cpiEvents(
+        await send(program.methods.withdrawSingle(false, new BN(1_000_000), new BN(1), null))
+      );
+      expect(event.name).to.equal("liquidityRemoved");
+      expect(event.data.amountX.toNumber()).to.equal(0);
+      expect(event.data.amountY.toNumber()).to.be.greaterThan(0);
+      expect(event.data.protocolFee.toNumber()).to.be.greaterThan(0);
+      await expectReserves(event.data);
+    });
+
+    it("emits Skimmed and Synced with the tracked reserves", async () => {
+      await transfer(connection, payer, pool.userXToken, pool.tokenXVault, payer, 1_000_000);
+      const [skimmed] = await cpiEvents(
+        await program.methods
+          .skim()
+          .accountsPartial({
+            ...pool,
+            recipientXToken: pool.userXToken,
+            recipientYToken: pool.userYToken,
+          })
+          .rpc({ commitment: "confirmed" })
+      );
+      expect(skimmed.name).to.equal("skimmed");
+      expect(skimmed.data.amountX.toNumber()).to.equal(1_000_000);
+      expect(skimmed.data.amountY.toNumber()).to.equal(0);
+      await expectReserves(skimmed.data);
+
+      await transfer(connection, payer, pool.userYToken, pool.tokenYVault, payer, 1_000_000);
+      const [synced] = await cpiEvents(await send(program.methods.sync()));
+      expect(synced.name).to.equal("synced");
+      await expectReserves(synced.data);
+    });
+
+    it("emits FlashLoanTaken and FlashLoanRepaid", async () => {
+      const repayIx = await program.methods.flashRepay().accountsPartial(pool).instruction();
+      const events = await cpiEvents(
+        await program.methods
+          .flashLoan(true, new BN(1_000_000))
+          .accountsPartial(pool)
+          .postInstructions([repayIx])
+          .rpc({ commitment: "confirmed" })
+      );
+      expect(events.map((event) => event.name)).to.deep.equal([
+        "flashLoanTaken",
+        "flashLoanRepaid",
+      ]);
+      expect(events[0].data.amount.toNumber()).to.equal(1_000_000);
+      expect(events[0].data.fee.toNumber()).to.equal(900);
+      expect(events[1].data.fee.toNumber()).to.equal(900);
+      await expectReserves(events[1].data);
+    });
+
+    it("emits ProtocolFeesClaimed with the amounts paid out", async () => {
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      const [event] = await cpiEvents(
+        await program.methods
+          .claimProtocolFees()
+          .accountsPartial({ ...pool, feeRecipient: payer.publicKey })
+          .rpc({ commitment: "confirmed" })
+      );
+      expect(event.name).to.equal("protocolFeesClaimed");
+      expect(event.data.amountX.toNumber()).to.equal(config.protocolFeesX.toNumber());
+      expect(event.data.amountY.toNumber()).to.equal(config.protocolFeesY.toNumber());
+      await expectReserves(event.data);
+    });
+  });
+
+  describe("ownership transfer", () => {
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+    const newOwner = anchor.web3.Keypair.generate();
+
+    const acceptOwner = (signer: anchor.web3.Keypair) =>
+      program.methods
+        .acceptOwner()
+        .accountsPartial({ user: signer.publicKey, poolConfig: pool.poolConfig })
+        .signers([signer])
+        .rpc();
+
+    const proposeOwner = (owner: anchor.web3.PublicKey | null) =>
+      program.methods
+        .proposeOwner(owner)
+        .accountsPartial({ user: payer.publicKey, poolConfig: pool.poolConfig })
+        .rpc({ commitment: "confirmed" });
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+    });
+
+    it("keeps the current owner until the proposal is accepted", async () => {
+      await proposeOwner(newOwner.publicKey);
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.owner.toBase58()).to.equal(payer.publicKey.toBase58());
+      expect(config.pendingOwner.toBase58()).to.equal(newOwner.publicKey.toBase58());
+    });
+
+    it("rejects acceptance by anyone but the proposed owner", async () => {
+      await expectError(acceptOwner(anchor.web3.Keypair.generate()), "InvalidAuthority");
+    });
+
+    it("replaces a proposal and announces each one", async () => {
+      const other = anchor.web3.Keypair.generate();
+      const events = await cpiEvents(await proposeOwner(other.publicKey));
+      expect(events.map((event) => event.name)).to.deep.equal(["ownershipProposed"]);
+      expect(events[0].data.owner.toBase58()).to.equal(payer.publicKey.toBase58());
+      expect(events[0].data.pendingOwner.toBase58()).to.equal(other.publicKey.toBase58());
+      // The earlier nominee can no longer take over
+      await expectError(acceptOwner(newOwner), "InvalidAuthority");
+    });
+
+    it("withdraws a proposal with None", async () => {
+      const events = await cpiEvents(await proposeOwner(null));
+      expect(events[0].data.pendingOwner).to.be.null;
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.pendingOwner).to.be.null;
+      await expectError(acceptOwner(newOwner), "InvalidAuthority");
+      await proposeOwner(newOwner.publicKey);
+    });
+
+    it("hands the pool over at the same address", async () => {
+      await acceptOwner(newOwner);
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.owner.toBase58()).to.equal(newOwner.publicKey.toBase58());
+      expect(config.pendingOwner).to.be.null;
+      await expectError(
+        program.methods
+          .updateConfig({ pauseFlags: { 0: EMERGENCY_MODE } })
+          .accountsPartial({ user: payer.publicKey, poolConfig: pool.poolConfig })
+          .rpc(),
+        "InvalidAuthority"
+      );
+    });
+  });
+
+  describe("admin roles", () => {
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+    const pauser = anchor.web3.Keypair.generate();
+    const feeManager = anchor.web3.Keypair.generate();
+    const emergencyAdmin = anchor.web3.Keypair.generate();
+
+    const updateConfig = (signer: anchor.web3.Keypair, param: object) =>
+      program.methods
+        .updateConfig(param)
+        .accountsPartial({ user: signer.publicKey, poolConfig: pool.poolConfig })
+        .signers([signer])
+        .rpc();
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+      for (const [role, holder] of [
+        ["pauser", pauser],
+        ["feeManager", feeManager],
+        ["emergencyAdmin", emergencyAdmin],
+      ] as const) {
+        await program.methods
+          .updateConfig({ [role]: { 0: holder.publicKey } })
+          .accountsPartial({ user: payer.publicKey, poolConfig: pool.poolConfig })
+          .rpc();
+      }
+    });
+
+    it("lets each role change only its own settings", async () => {
+      await updateConfig(pauser, { pauseFlags: { 0: PAUSE_SWAP } });
+      await updateConfig(feeManager, { lpFee: { 0: 50 } });
+      await updateConfig(emergencyAdmin, { pauseFlags: { 0: PAUSE_SWAP | EMERGENCY_MODE } });
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.pauseFlags).to.equal(PAUSE_SWAP | EMERGENCY_MODE);
+      expect(config.lpFee).to.equal(50);
+    });
+
+    it("rejects a role acting outside its remit", async () => {
+      await expectError(updateConfig(pauser, { lpFee: { 0: 10 } }), "InvalidAuthority");
+      await expectError(updateConfig(feeManager, { pauseFlags: { 0: 0 } }), "InvalidAuthority");
+      // Clearing emergency mode is not the pauser's call
+      await expectError(
+        updateConfig(pauser, { pauseFlags: { 0: PAUSE_SWAP } }),
+        "InvalidAuthority"
+      );
+      await expectError(
+        updateConfig(emergencyAdmin, { pauseFlags: { 0: EMERGENCY_MODE } }),
+        "InvalidAuthority"
+      );
+    });
+
+    it("keeps role assignment with the owner", async () => {
+      await expectError(
+        updateConfig(pauser, { feeManager: { 0: pauser.publicKey } }),
+        "InvalidAuthority"
+      );
+    });
+
+    it("still lets the owner act in every role", async () => {
+      await program.methods
+        .updateConfig({ pauseFlags: { 0: 0 } })
+        .accountsPartial({ user: payer.publicKey, poolConfig: pool.poolConfig })
+        .rpc();
+      const config = await program.account.poolConfig.fetch(pool.poolConfig);
+      expect(config.pauseFlags).to.equal(0);
+    });
+  });
+
+  describe("dynamic fees", () => {
+    let dynamic: Awaited<ReturnType<typeof createFundedPool>>;
+    let flat: Awaited<ReturnType<typeof createFundedPool>>;
+
+    const swapOut = async (accounts: typeof dynamic, amount: number) => {
+      const before = await balance(accounts.userYToken);
+      await program.methods
+        .swap(true, new BN(amount), new BN(1), null)
+        .accountsPartial(accounts)
+        .rpc();
+      return (await balance(accounts.userYToken)) - before;
+    };
+
+    before(async () => {
+      dynamic = await createFundedPool({ constantProduct: {} }, 0);
+      flat = await createFundedPool({ constantProduct: {} }, 0);
+      await program.methods
+        .updateConfig({ dynamicFee: { 0: { minFee: 10, maxFee: 100 } } })
+        .accountsPartial({ user: payer.publicKey, poolConfig: dynamic.poolConfig })
+        .rpc();
+    });
+
+    it("charges the minimum fee while the price is calm", async () => {
+      // Both pools start at the same price; only the fee separates them
+      expect(await swapOut(dynamic, 100_000_000)).to.be.greaterThan(
+        await swapOut(flat, 100_000_000)
+      );
+    });
+
+    it("raises the fee after a large price move", async () => {
+      const config = await program.account.poolConfig.fetch(dynamic.poolConfig);
+      expect(config.volatility.toNumber()).to.be.greaterThan(0);
+      expect(await swapOut(dynamic, 10_000_000)).to.be.lessThan(await swapOut(flat, 10_000_000));
+    });
+
+    it("rejects bounds above the cap or out of order", async () => {
+      for (const bounds of [
+        { minFee: 10, maxFee: 1_001 },
+        { minFee: 50, maxFee: 20 },
+      ]) {
+        await expectError(
+          program.methods
+            .updateConfig({ dynamicFee: { 0: bounds } })
+            .accountsPartial({ user: payer.publicKey, poolConfig: dynamic.poolConfig })
+            .rpc(),
+          "InvalidFeeBounds"
+        );
+      }
+    });
+
+    it("rejects changes from anyone but the owner", async () => {
+      const stranger = anchor.web3.Keypair.generate();
+      await expectError(
+        program.methods
+          .updateConfig({ dynamicFee: { 0: null } })
+          .accountsPartial({ user: stranger.publicKey, poolConfig: dynamic.poolConfig })
+          .signers([stranger])
+          .rpc(),
+        "InvalidAuthority"
+      );
+    });
+  });
+
+  describe("lp farming", () => {
+    let pool: Awaited<ReturnType<typeof createFundedPool>>;
+    let rewardMint: anchor.web3.PublicKey;
+    let farm: anchor.web3.PublicKey;
+    let userReward: anchor.web3.PublicKey;
+    let createSignature: string;
+
+    const farmAccounts = () => ({
+      ...pool,
+      user: payer.publicKey,
+      owner: payer.publicKey,
+      farm,
+      rewardMint,
+      farmLpVault: getAssociatedTokenAddressSync(pool.lpToken, farm, true),
+      rewardVault: getAssociatedTokenAddressSync(rewardMint, farm, true),
+      userRewardToken: userReward,
+      ownerRewardToken: getAssociatedTokenAddressSync(rewardMint, payer.publicKey),
+      tokenProgram: TOKEN_PROGRAM_ID,
+      rewardTokenProgram: TOKEN_PROGRAM_ID,
+    });
+
+    before(async () => {
+      pool = await createFundedPool({ constantProduct: {} }, 0);
+      rewardMint = await createMint(connection, payer, payer.publicKey, null, 6);
+      const ata = await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        rewardMint,
+        payer.publicKey
+      );
+      await mintTo(connection, payer, rewardMint, ata.address, payer, 1_000_000_000);
+      [farm] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("farm"), pool.poolConfig.toBuffer()],
+        program.programId
+      );
+      userReward = ata.address;
+      createSignature = await program.methods
+        .createFarm(new BN(0))
+        .accountsPartial(farmAccounts())
+        .rpc({ commitment: "confirmed" });
+    });
+
+    it("emits FarmCreated when the farm opens", async () => {
+      const events = await cpiEvents(createSignature);
+      expect(events.map((event) => event.name)).to.deep.equal(["farmCreated"]);
+      expect(events[0].data.farm.toBase58()).to.equal(farm.toBase58());
+      expect(events[0].data.poolConfig.toBase58()).to.equal(pool.poolConfig.toBase58());
+      expect(events[0].data.rewardMint.toBase58()).to.equal(rewardMint.toBase58());
+      expect(events[0].data.emissionRate.toNumber()).to.equal(0);
+    });
+
+    it("emits funded rewards to stakers", async () => {
+      const funded = await cpiEvents(
+        await program.methods
+          .fundRewards(new BN(100_000_000), new BN(1_000_000))
+          .accountsPartial(farmAccounts())
+          .rpc({ commitment: "confirmed" })
+      );
+      expect(funded.map((event) => event.name)).to.deep.equal(["rewardsFunded"]);
+      expect(funded[0].data.amount.toNumber()).to.equal(100_000_000);
+      expect(funded[0].data.emissionRate.toNumber()).to.equal(1_000_000);
+
+      const staked = await cpiEvents(
+        await program.methods
+          .stake(new BN(10_000_000))
+          .accountsPartial(farmAccounts())
+          .rpc({ commitment: "confirmed" })
+      );
+      expect(staked.map((event) => event.name)).to.deep.equal(["staked"]);
+      expect(staked[0].data.totalStaked.toNumber()).to.equal(10_000_000);
+      expect(await balance(farmAccounts().farmLpVault)).to.equal(10_000_000);
+
+      await new Promise((resolve) => setTimeout(resolve, 2_000));
+      const before = await balance(userReward);
+      const claimedEvents = await cpiEvents(
+        await program.methods
+          .claimRewards()
+          .accountsPartial(farmAccounts())
+          .rpc({ commitment: "confirmed" })
+      );
+      const claimed = (await balance(userReward)) - before;
+      expect(claimedEvents.map((event) => event.name)).to.deep.equal(["rewardsClaimed"]);
+      expect(claimedEvents[0].data.amount.toNumber()).to.equal(claimed);
+      // The only staker takes everything emitted, within rounding
+      expect(claimed).to.be.greaterThan(0);
+      expect(claimed).to.be.at.most(100_000_000);
+
+      const state = await program.account.farm.fetch(farm);
+      expect(state.totalStaked.toNumber()).to.equal(10_000_000);
+      expect(state.rewardsRemaining.toNumber()).to.be.at.most(100_000_000 - claimed);
+    });
+
+    it("rejects unstaking more than is staked", async () => {
+      await expectError(
+        program.methods.unstake(new BN(10_000_001)).accountsPartial(farmAccounts()).rpc(),
+        "InsufficientStake"
+      );
+    });
+
+    it("returns the LP on unstake", async () => {
+      const before = await balance(pool.userLpTokenAc);
+      const events = await cpiEvents(
+        await program.methods
+          .unstake(new BN(10_000_000))
+          .accountsPartial(farmAccounts())
+          .rpc({ commitment: "confirmed" })
+      );
+      expect((await balance(pool.userLpTokenAc)) - before).to.equal(10_000_000);
+      expect(events.map((event) => event.name)).to.deep.equal(["unstaked"]);
+      expect(events[0].data.totalStaked.toNumber()).to.equal(0);
+      const position = await program.account.stakePosition.fetch(
+        anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from("stake-position"), farm.toBuffer(), payer.publicKey.toBuffer()],
+          program.programId
+        )[0]
+      );
+      expect(position.amount.toNumber()).to.equal(0);
+    });
+
+    it("splits rewards pro rata by stake and time", async () => {
+      // Alice stakes 30M alone, then Bob joins with 10M, so from then on Alice earns
+      // three quarters of the 1M per second emission and Bob one quarter
+      const [alice, bob] = [anchor.web3.Keypair.generate(), anchor.web3.Keypair.generate()];
+      const stakers = await Promise.all(
+        [alice, bob].map(async (staker) => {
+          await connection.confirmTransaction(
+            await connection.requestAirdrop(staker.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+          );
+          const lp = (
+            await getOrCreateAssociatedTokenAccount(
+              connection,
+              payer,
+              pool.lpToken,
+              staker.publicKey
+            )
+          ).address;
+          const reward = getAssociatedTokenAddressSync(rewardMint, staker.publicKey);
+          const accounts = {
+            ...farmAccounts(),
+            user: staker.publicKey,
+            userLpTokenAc: lp,
+            userRewardToken: reward,
+          };
+          return { staker, lp, reward, accounts };
+        })
+      );
+      const [a, b] = stakers;
+      await transfer(connection, payer, pool.userLpTokenAc, a.lp, payer, 30_000_000);
+      await transfer(connection, payer, pool.userLpTokenAc, b.lp, payer, 10_000_000);
+      const lastUpdate = async () =>
+        (await program.account.farm.fetch(farm)).lastUpdate.toNumber();
+      const send = (
+        method: { accountsPartial: (accounts: object) => any },
+        { accounts, staker }: (typeof stakers)[number]
+      ) => method.accountsPartial(accounts).signers([staker]).rpc();
+
+      await send(program.methods.stake(new BN(30_000_000)), a);
+      const aliceStaked = await lastUpdate();
+      await send(program.methods.stake(new BN(10_000_000)), b);
+      const bobStaked = await lastUpdate();
+      await new Promise((resolve) => setTimeout(resolve, 2_000));
+      await send(program.methods.claimRewards(), a);
+      const aliceClaimed = await lastUpdate();
+      await send(program.methods.claimRewards(), b);
+      const bobClaimed = await lastUpdate();
+
+      const rate = 1_000_000;
+      const aliceExpected =
+        rate * (bobStaked - aliceStaked) + (rate * (aliceClaimed - bobStaked) * 3) / 4;
+      const bobExpected = (rate * (bobClaimed - bobStaked)) / 4;
+      expect(bobClaimed).to.be.greaterThan(bobStaked);
+      // Within the accumulator's per-update rounding
+      expect(await balance(a.reward)).to.be.closeTo(aliceExpected, 3);
+      expect(await balance(b.reward)).to.be.closeTo(bobExpected, 3);
+    });
+
+    it("rejects a farm created by anyone but the owner", async () => {
+      const other = await createFundedPool({ constantProduct: {} }, 0, 0);
+      const stranger = anchor.web3.Keypair.generate();
+      await connection.confirmTransaction(
+        await connection.requestAirdrop(stranger.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+      );
+      const [otherFarm] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("farm"), other.poolConfig.toBuffer()],
+        program.programId
+      );
+      await expectError(
+        program.methods
+          .createFarm(new BN(0))
+          .accountsPartial({
+            owner: stranger.publicKey,
+            poolConfig: other.poolConfig,
+            lpToken: other.lpToken,
+            rewardMint,
+            farm: otherFarm,
+            farmLpVault: getAssociatedTokenAddressSync(other.lpToken, otherFarm, true),
+            rewardVault: getAssociatedTokenAddressSync(rewardMint, otherFarm, true),
+            tokenProgram: TOKEN_PROGRAM_ID,
+            rewardTokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([stranger])
+          .rpc(),
+        "InvalidAuthority"
+      );
+    });
+
+    it("rejects funding from anyone but the owner", async () => {
+      const stranger = anchor.web3.Keypair.generate();
+      const strangerReward = await getOrCreateAssociatedTokenAccount(
+        connection,
+        payer,
+        rewardMint,
+        stranger.publicKey
+      );
+      await expectError(
+        program.methods
+          .fundRewards(new BN(0), new BN(1))
+          .accountsPartial({
+            ...farmAccounts(),
+            owner: stranger.publicKey,
+            ownerRewardToken: strangerReward.address,
+          })
+          .signers([stranger])
+          .rpc(),
+        "InvalidAuthority"
+      );
+    });
+  });
+
+  describe("lp token metadata", () => {
+    const METADATA_PROGRAM_ID = new anchor.web3.PublicKey(
+      "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s"
+    );
+
+    const metadataAddress = (mint: anchor.web3.PublicKey) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("metadata"), METADATA_PROGRAM_ID.toBuffer(), mint.toBuffer()],
+        METADATA_PROGRAM_ID
+      )[0];
+
+    // Metaplex metadata: key (1), update authority (32), mint (32), then the
+    // length-prefixed name and symbol, padded with zero bytes
+    const readMetadata = async (mint: anchor.web3.PublicKey) => {
+      const info = await connection.getAccountInfo(metadataAddress(mint));
+      expect(info.owner.toBase58()).to.equal(METADATA_PROGRAM_ID.toBase58());
+      const readString = (offset: number) => {
+        const length = info.data.readUInt32LE(offset);
+        const value = info.data.subarray(offset + 4, offset + 4 + length).toString();
+        return [value.replace(/\0/g, ""), offset + 4 + length] as const;
+      };
+      const [name, symbolOffset] = readString(65);
+      const [symbol] = readString(symbolOffset);
+      return {
+        updateAuthority: new anchor.web3.PublicKey(info.data.subarray(1, 33)),
+        mint: new anchor.web3.PublicKey(info.data.subarray(33, 65)),
+        name,
+        symbol,
+      };
+    };
+
+    const borshString = (value: string) => {
+      const length = Buffer.alloc(4);
+      length.writeUInt32LE(Buffer.byteLength(value));
+      return Buffer.concat([length, Buffer.from(value)]);
+    };
+
+    // create_metadata_account_v3 for an underlying mint, with the payer as authority
+    const createMetaplexMetadata = (mint: anchor.web3.PublicKey, symbol: string) =>
+      provider.sendAndConfirm(
+        new anchor.web3.Transaction().add(
+          new anchor.web3.TransactionInstruction({
+            programId: METADATA_PROGRAM_ID,
+            keys: [
+              { pubkey: metadataAddress(mint), isSigner: false, isWritable: true },
+              { pubkey: mint, isSigner: false, isWritable: false },
+              { pubkey: payer.publicKey, isSigner: true, isWritable: true },
+              { pubkey: payer.publicKey, isSigner: true, isWritable: true },
+              { pubkey: payer.publicKey, isSigner: true, isWritable: false },
+              {
+                pubkey: anchor.web3.SystemProgram.programId,
+                isSigner: false,
+                isWritable: false,
+              },
+              { pubkey: anchor.web3.SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
+            ],
+            data: Buffer.concat([
+              Buffer.from([33]),
+              borshString(`${symbol} Token`),
+              borshString(symbol),
+              borshString(""),
+              // No seller fee, creators, collection or uses; mutable; no collection details
+              Buffer.from([0, 0, 0, 0, 0, 1, 0]),
+            ]),
+          })
+        )
+      );
+
+    it("names the LP mint after its pair with the pool as update authority", async () => {
+      const pool = await createFundedPool({ constantProduct: {} }, 0, 0);
+      const metadata = await readMetadata(pool.lpToken);
+      expect(metadata.updateAuthority.toBase58()).to.equal(pool.poolConfig.toBase58());
+      expect(metadata.mint.toBase58()).to.equal(pool.lpToken.toBase58());
+      // Neither side has metadata, so the name falls back to address prefixes
+      const x = pool.tokenXMint.toBase58().slice(0, 4);
+      const y = pool.tokenYMint.toBase58().slice(0, 4);
+      expect(metadata.name).to.equal(`${x}-${y} LP`);
+      expect(metadata.symbol).to.equal(`${(x + y).slice(0, 7)}-LP`);
+    });
+
+    it("takes the pair's symbols from their Metaplex metadata", async () => {
+      const a = await createMint(connection, payer, payer.publicKey, null, 6);
+      const b = await createMint(connection, payer, payer.publicKey, null, 6);
+      const [x, y] = Buffer.compare(a.toBuffer(), b.toBuffer()) < 0 ? [a, b] : [b, a];
+      await createMetaplexMetadata(x, "SOL");
+      await createMetaplexMetadata(y, "USDC");
+      const [pool] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("pool-config"), x.toBuffer(), y.toBuffer(), seeds.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [lp] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("lp-token"), pool.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .initialize(seeds, 30, payer.publicKey, { constantProduct: {} }, new BN(0))
+        .accountsPartial({
+          owner: payer.publicKey,
+          tokenXMint: x,
+          tokenYMint: y,
+          tokenXMetadata: metadataAddress(x),
+          tokenYMetadata: metadataAddress(y),
+          poolConfig: pool,
+          lpToken: lp,
+          tokenXVault: getAssociatedTokenAddressSync(x, pool, true),
+          tokenYVault: getAssociatedTokenAddressSync(y, pool, true),
+          tokenXProgram: TOKEN_PROGRAM_ID,
+          tokenYProgram: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      const metadata = await readMetadata(lp);
+      expect(metadata.name).to.equal("SOL-USDC LP");
+      expect(metadata.symbol).to.equal("SOLUSDC-LP");
+    });
   });
 });